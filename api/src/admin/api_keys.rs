@@ -0,0 +1,171 @@
+use actix_web::{delete, get, post, HttpResponse, web};
+use actix_web::web::Data;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::api_key;
+use entity::prelude::{ApiKey, Event};
+use crate::auth::{ApiKeyIdentity, ApiScope, format_scopes, generate_key, parse_scopes};
+use crate::status;
+
+/// Lists API keys. Keys bound to an event only see the keys of that event.
+#[get("/api-keys")]
+pub(crate) async fn get_api_keys(data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    let db = data.get_ref();
+
+    let mut select = ApiKey::find().order_by_asc(api_key::Column::CreatedAt);
+    if let Some(event_id) = identity.event_id {
+        select = select.filter(api_key::Column::EventId.eq(event_id));
+    }
+
+    match select.all(db).await {
+        Ok(keys) => HttpResponse::Ok().json(GetApiKeysResponse {
+            data: keys.into_iter().map(ApiKeyInfo::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting API keys from DB: {}", e);
+            status::err_server("Error getting API keys from DB")
+        }
+    }
+}
+
+/// Issues a new API key. The key itself is only ever returned in this response.
+///
+/// Keys issued by a key bound to an event are bound to the same event.
+#[post("/api-keys")]
+pub(crate) async fn create_api_key(body: web::Json<CreateApiKeyRequest>, data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    let db = data.get_ref();
+    let request = body.into_inner();
+
+    let event_id = match (identity.event_id, request.event_id) {
+        (Some(bound), Some(requested)) if bound != requested => {
+            return status::err_forbidden("This API key cannot issue keys for other events");
+        }
+        (Some(bound), _) => Some(bound),
+        (None, requested) => requested,
+    };
+
+    if let Some(event_id) = event_id {
+        match Event::find_by_id(event_id).one(db).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return status::err_bad_request("event_id does not exist"),
+            Err(e) => {
+                log::error!("Error getting event from DB: {}", e);
+                return status::err_server("Error getting event from DB");
+            }
+        }
+    }
+
+    if request.name.trim().is_empty() {
+        return status::err_bad_request("name must not be empty");
+    }
+    if request.scopes.is_empty() {
+        return status::err_bad_request("scopes must not be empty");
+    }
+    if request.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return status::err_bad_request("expires_at must be in the future");
+    }
+
+    let id = Uuid::new_v4();
+    let (key, hash) = generate_key(id);
+
+    let result = api_key::ActiveModel {
+        id: Set(id),
+        event_id: Set(event_id),
+        name: Set(request.name),
+        key_hash: Set(hash),
+        scopes: Set(format_scopes(&request.scopes)),
+        expires_at: Set(request.expires_at),
+        ..Default::default()
+    }.insert(db).await;
+
+    match result {
+        Ok(model) => {
+            log::info!("Admin: issued API key {} ({}) with scopes {}", model.id, model.name, model.scopes);
+            HttpResponse::Created().json(CreateApiKeyResponse {
+                key,
+                info: ApiKeyInfo::from(model),
+            })
+        }
+        Err(e) => {
+            log::error!("Error creating API key: {}", e);
+            status::err_server("Error creating API key")
+        }
+    }
+}
+
+#[delete("/api-keys/{id}")]
+pub(crate) async fn delete_api_key(info: web::Path<Uuid>, data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    let db = data.get_ref();
+    let id = info.into_inner();
+
+    let result = ApiKey::find_by_id(id).one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting API key from DB: {}", e);
+        return status::err_server("Error getting API key from DB");
+    }
+
+    let key = match result.unwrap() {
+        Some(key) if identity.event_id.is_none() || key.event_id == identity.event_id => key,
+        _ => return status::err_not_found(),
+    };
+
+    log::info!("Admin: revoking API key {} ({})", key.id, key.name);
+    match key.delete(db).await {
+        Ok(_) => status::success(),
+        Err(e) => {
+            log::error!("Error deleting API key: {}", e);
+            status::err_server("Error deleting API key")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateApiKeyRequest {
+    pub name: String,
+    /// Restricts the key to an event, `None` for a key that can access every event.
+    #[serde(default)]
+    pub event_id: Option<Uuid>,
+    pub scopes: Vec<ApiScope>,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Serialize)]
+struct CreateApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
+#[derive(Serialize)]
+struct GetApiKeysResponse {
+    pub data: Vec<ApiKeyInfo>,
+}
+
+#[derive(Serialize)]
+struct ApiKeyInfo {
+    pub id: Uuid,
+    pub event_id: Option<Uuid>,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<api_key::Model> for ApiKeyInfo {
+    fn from(key: api_key::Model) -> Self {
+        Self {
+            id: key.id,
+            event_id: key.event_id,
+            name: key.name,
+            scopes: parse_scopes(&key.scopes),
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            created_at: key.created_at,
+        }
+    }
+}
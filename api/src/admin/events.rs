@@ -0,0 +1,227 @@
+use actix_web::{delete, get, patch, post, HttpResponse, web};
+use actix_web::web::Data;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::event;
+use entity::prelude::Event;
+use rusty_interaction::types::Snowflake;
+use crate::{events, status};
+use crate::auth::ApiKeyIdentity;
+
+const DEFAULT_MAX_ACCOUNTS: u32 = 1;
+
+#[get("/events")]
+pub(crate) async fn get_events(data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    let db = data.get_ref();
+
+    match Event::find().order_by_asc(event::Column::CreatedAt).all(db).await {
+        Ok(events) => HttpResponse::Ok().json(GetEventsResponse {
+            data: events.into_iter().map(EventInfo::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting events from DB: {}", e);
+            status::err_server("Error getting events from DB")
+        }
+    }
+}
+
+#[post("/events")]
+pub(crate) async fn create_event(body: web::Json<CreateEventRequest>, data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    let db = data.get_ref();
+    let request = body.into_inner();
+
+    if request.name.trim().is_empty() {
+        return status::err_bad_request("name must not be empty");
+    }
+
+    match events::find_by_guild(db, request.guild_id).await {
+        Ok(Some(_)) => return status::err_conflict("There already is an event for this guild"),
+        Ok(None) => {}
+        Err(e) => {
+            log::error!("Error getting event from DB: {}", e);
+            return status::err_server("Error getting event from DB");
+        }
+    }
+
+    let result = event::ActiveModel {
+        name: Set(request.name),
+        guild_id: Set(request.guild_id as i64),
+        moderator_roles: Set(events::format_moderator_roles(&request.moderator_roles)),
+        webhook_url: Set(request.webhook_url),
+        max_accounts: Set(request.max_accounts.unwrap_or(DEFAULT_MAX_ACCOUNTS) as i32),
+        account_limits: Set(format_account_limits(&request.account_limits)),
+        ..Default::default()
+    }.insert(db).await;
+
+    match result {
+        Ok(event) => {
+            log::info!("Admin: created event {} ({}) for guild {}", event.id, event.name, event.guild_id);
+            HttpResponse::Created().json(EventInfo::from(event))
+        }
+        Err(e) => {
+            log::error!("Error creating event: {}", e);
+            status::err_server("Error creating event")
+        }
+    }
+}
+
+#[patch("/events/{id}")]
+pub(crate) async fn update_event(info: web::Path<Uuid>, body: web::Json<UpdateEventRequest>, data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    let db = data.get_ref();
+    let id = info.into_inner();
+    let request = body.into_inner();
+
+    let result = Event::find_by_id(id).one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting event from DB: {}", e);
+        return status::err_server("Error getting event from DB");
+    }
+
+    let event = match result.unwrap() {
+        Some(event) => event,
+        None => return status::err_not_found(),
+    };
+
+    let mut event: event::ActiveModel = event.into();
+    if let Some(name) = request.name {
+        event.name = Set(name);
+    }
+    if let Some(roles) = request.moderator_roles {
+        event.moderator_roles = Set(events::format_moderator_roles(&roles));
+    }
+    if let Some(webhook_url) = request.webhook_url {
+        event.webhook_url = Set(Some(webhook_url).filter(|url| !url.is_empty()));
+    }
+    if let Some(max_accounts) = request.max_accounts {
+        event.max_accounts = Set(max_accounts as i32);
+    }
+    if let Some(limits) = request.account_limits {
+        event.account_limits = Set(format_account_limits(&limits));
+    }
+
+    match event.update(db).await {
+        Ok(event) => HttpResponse::Ok().json(EventInfo::from(event)),
+        Err(e) => {
+            log::error!("Error updating event: {}", e);
+            status::err_server("Error updating event")
+        }
+    }
+}
+
+/// Deletes an event together with all of its links and API keys.
+#[delete("/events/{id}")]
+pub(crate) async fn delete_event(info: web::Path<Uuid>, data: Data<DatabaseConnection>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    let db = data.get_ref();
+    let id = info.into_inner();
+
+    let result = Event::find_by_id(id).one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting event from DB: {}", e);
+        return status::err_server("Error getting event from DB");
+    }
+
+    let event = match result.unwrap() {
+        Some(event) => event,
+        None => return status::err_not_found(),
+    };
+
+    log::info!("Admin: deleting event {} ({})", event.id, event.name);
+    match event.delete(db).await {
+        Ok(_) => status::success(),
+        Err(e) => {
+            log::error!("Error deleting event: {}", e);
+            status::err_server("Error deleting event")
+        }
+    }
+}
+
+fn format_account_limits(limits: &[AccountLimit]) -> String {
+    events::format_account_limits(&limits.iter().map(|limit| (limit.role, limit.max_accounts)).collect::<Vec<_>>())
+}
+
+fn forbidden() -> HttpResponse {
+    status::err_forbidden("Events can only be managed with a key that is not bound to an event")
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateEventRequest {
+    pub name: String,
+    pub guild_id: Snowflake,
+    #[serde(default)]
+    pub moderator_roles: Vec<Snowflake>,
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// How many Minecraft accounts each Discord user may link, 1 by default.
+    #[serde(default)]
+    pub max_accounts: Option<u32>,
+    #[serde(default)]
+    pub account_limits: Vec<AccountLimit>,
+}
+
+/// Only the given fields are changed, an empty `webhook_url` removes the webhook.
+#[derive(Deserialize)]
+pub(crate) struct UpdateEventRequest {
+    pub name: Option<String>,
+    pub moderator_roles: Option<Vec<Snowflake>>,
+    pub webhook_url: Option<String>,
+    pub max_accounts: Option<u32>,
+    pub account_limits: Option<Vec<AccountLimit>>,
+}
+
+/// Members with the role may link this many accounts instead of the event's `max_accounts`.
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AccountLimit {
+    pub role: Snowflake,
+    pub max_accounts: u32,
+}
+
+#[derive(Serialize)]
+struct GetEventsResponse {
+    pub data: Vec<EventInfo>,
+}
+
+#[derive(Serialize)]
+struct EventInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub guild_id: Snowflake,
+    pub moderator_roles: Vec<Snowflake>,
+    pub webhook_url: Option<String>,
+    pub max_accounts: u32,
+    pub account_limits: Vec<AccountLimit>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<event::Model> for EventInfo {
+    fn from(event: event::Model) -> Self {
+        let account_limits = events::account_limits(&event).into_iter()
+            .map(|(role, max_accounts)| AccountLimit { role, max_accounts })
+            .collect();
+
+        Self {
+            id: event.id,
+            name: event.name.clone(),
+            guild_id: event.guild_id as Snowflake,
+            moderator_roles: events::moderator_roles(&event),
+            webhook_url: event.webhook_url,
+            max_accounts: event.max_accounts.max(0) as u32,
+            account_limits,
+            created_at: event.created_at,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{get, HttpResponse, web};
+use actix_web::web::Data;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::link_history;
+use entity::link_history::LinkAction;
+use entity::prelude::LinkHistory;
+use rusty_interaction::types::Snowflake;
+use crate::status;
+use crate::events::CurrentEvent;
+
+const DEFAULT_LIMIT: u64 = 100;
+const MAX_LIMIT: u64 = 1000;
+
+/// Lists link history entries of an event, newest first, for a Discord user and/or a Minecraft account.
+#[get("/history")]
+pub(crate) async fn get_history(query: web::Query<HistoryQuery>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let query = query.into_inner();
+
+    if query.snowflake.is_none() && query.uuid.is_none() {
+        return status::err_bad_request("Either snowflake or uuid must be provided");
+    }
+
+    let mut condition = Condition::any();
+    if let Some(snowflake) = query.snowflake {
+        condition = condition.add(link_history::Column::DiscordSnowflake.eq(snowflake as i64));
+    }
+    if let Some(uuid) = query.uuid {
+        condition = condition
+            .add(link_history::Column::OldUuid.eq(uuid))
+            .add(link_history::Column::NewUuid.eq(uuid));
+    }
+
+    let result = LinkHistory::find()
+        .filter(link_history::Column::EventId.eq(event.0.id))
+        .filter(condition)
+        .order_by_desc(link_history::Column::Id)
+        .limit(query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .all(db).await;
+
+    match result {
+        Ok(entries) => HttpResponse::Ok().json(GetHistoryResponse {
+            data: entries.into_iter().map(HistoryEntry::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting link history from DB: {}", e);
+            status::err_server("Error getting link history from DB")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct HistoryQuery {
+    pub snowflake: Option<Snowflake>,
+    pub uuid: Option<Uuid>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct HistoryEntry {
+    pub id: i64,
+    pub snowflake: Snowflake,
+    pub actor: Option<Snowflake>,
+    pub action: LinkAction,
+    pub old_uuid: Option<Uuid>,
+    pub new_uuid: Option<Uuid>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<link_history::Model> for HistoryEntry {
+    fn from(entry: link_history::Model) -> Self {
+        Self {
+            id: entry.id,
+            snowflake: entry.discord_snowflake as Snowflake,
+            actor: entry.actor_snowflake.map(|actor| actor as Snowflake),
+            action: entry.action,
+            old_uuid: entry.old_uuid,
+            new_uuid: entry.new_uuid,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GetHistoryResponse {
+    pub data: Vec<HistoryEntry>,
+}
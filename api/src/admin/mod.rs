@@ -0,0 +1,9 @@
+pub(crate) mod api_keys;
+pub(crate) mod events;
+pub(crate) mod history;
+pub(crate) mod names;
+pub(crate) mod notifications;
+pub(crate) mod rcon;
+pub(crate) mod stats;
+pub(crate) mod users;
+pub(crate) mod webhooks;
\ No newline at end of file
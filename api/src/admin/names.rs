@@ -0,0 +1,28 @@
+use actix_web::{get, post, HttpResponse, web};
+use actix_web::web::Data;
+use crate::auth::ApiKeyIdentity;
+use crate::names::NameRefresh;
+use crate::status;
+
+#[get("/names/refresh")]
+pub(crate) async fn get_name_refresh(refresh: Data<NameRefresh>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    HttpResponse::Ok().json(refresh.status().await)
+}
+
+/// Refreshes the stored Minecraft usernames of all events in the background. Responds with the
+/// status of the last refresh, including how many accounts failed.
+#[post("/names/refresh")]
+pub(crate) async fn trigger_name_refresh(refresh: Data<NameRefresh>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return forbidden();
+    }
+    refresh.trigger();
+    HttpResponse::Accepted().json(refresh.status().await)
+}
+
+fn forbidden() -> HttpResponse {
+    status::err_forbidden("Names of all events can only be refreshed with a key that is not bound to an event")
+}
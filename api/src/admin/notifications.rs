@@ -0,0 +1,113 @@
+use actix_web::{get, post, HttpResponse, web};
+use actix_web::web::Data;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::notification_outbox;
+use entity::prelude::NotificationOutbox;
+use crate::status;
+use crate::events::CurrentEvent;
+
+const DEFAULT_LIMIT: u64 = 100;
+const MAX_LIMIT: u64 = 1000;
+
+/// Lists the webhook notifications of an event that were given up on, newest first.
+#[get("/notifications/failed")]
+pub(crate) async fn get_failed_notifications(query: web::Query<FailedNotificationsQuery>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+
+    let result = NotificationOutbox::find()
+        .filter(notification_outbox::Column::EventId.eq(event.0.id))
+        .filter(notification_outbox::Column::FailedAt.is_not_null())
+        .order_by_desc(notification_outbox::Column::Id)
+        .limit(query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .all(db).await;
+
+    match result {
+        Ok(notifications) => HttpResponse::Ok().json(GetNotificationsResponse {
+            data: notifications.into_iter().map(NotificationInfo::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting notifications from DB: {}", e);
+            status::err_server("Error getting notifications from DB")
+        }
+    }
+}
+
+/// Queues a failed notification again, with a fresh set of attempts.
+#[post("/notifications/{id}/retry")]
+pub(crate) async fn retry_notification(info: web::Path<i64>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let id = info.into_inner();
+
+    let result = NotificationOutbox::find_by_id(id)
+        .filter(notification_outbox::Column::EventId.eq(event.0.id))
+        .one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting notification from DB: {}", e);
+        return status::err_server("Error getting notification from DB");
+    }
+
+    let notification = match result.unwrap() {
+        Some(notification) => notification,
+        None => return status::err_not_found(),
+    };
+    if notification.failed_at.is_none() {
+        return status::err_conflict("Only failed notifications can be retried");
+    }
+
+    log::info!("Admin: retrying notification {}", notification.id);
+    let mut notification: notification_outbox::ActiveModel = notification.into();
+    notification.attempts = Set(0);
+    notification.next_attempt_at = Set(Utc::now().into());
+    notification.failed_at = Set(None);
+
+    match notification.update(db).await {
+        Ok(notification) => HttpResponse::Ok().json(NotificationInfo::from(notification)),
+        Err(e) => {
+            log::error!("Error updating notification: {}", e);
+            status::err_server("Error updating notification")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FailedNotificationsQuery {
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct GetNotificationsResponse {
+    pub data: Vec<NotificationInfo>,
+}
+
+#[derive(Serialize)]
+struct NotificationInfo {
+    pub id: i64,
+    pub event_id: Uuid,
+    /// The message as it is sent to the webhook.
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTimeWithTimeZone,
+    pub failed_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<notification_outbox::Model> for NotificationInfo {
+    fn from(notification: notification_outbox::Model) -> Self {
+        Self {
+            id: notification.id,
+            event_id: notification.event_id,
+            payload: serde_json::from_str(&notification.payload).unwrap_or(serde_json::Value::Null),
+            attempts: notification.attempts,
+            last_error: notification.last_error,
+            next_attempt_at: notification.next_attempt_at,
+            failed_at: notification.failed_at,
+            created_at: notification.created_at,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use actix_web::{delete, get, post, HttpResponse, web};
+use actix_web::web::Data;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, QueryOrder, SqlErr};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::rcon_server;
+use entity::prelude::RconServer;
+use crate::{rcon, status};
+use crate::events::CurrentEvent;
+
+#[get("/rcon")]
+pub(crate) async fn get_rcon_servers(data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+
+    let result = RconServer::find()
+        .filter(rcon_server::Column::EventId.eq(event.0.id))
+        .order_by_asc(rcon_server::Column::CreatedAt)
+        .all(db).await;
+
+    match result {
+        Ok(servers) => HttpResponse::Ok().json(GetRconServersResponse {
+            data: servers.into_iter().map(RconServerInfo::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting RCON servers from DB: {}", e);
+            status::err_server("Error getting RCON servers from DB")
+        }
+    }
+}
+
+/// Adds a Minecraft server the link changes of the event are pushed to. The address is given as
+/// `host[:port]`, with IPv6 hosts in brackets and the port defaulting to 25575.
+#[post("/rcon")]
+pub(crate) async fn create_rcon_server(body: web::Json<CreateRconServerRequest>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let request = body.into_inner();
+
+    let server = match rcon::RconServer::new(&request.address, request.password.clone()) {
+        Ok(server) => server,
+        Err(e) => return status::err_bad_request(&e.to_string()),
+    };
+
+    let result = rcon_server::ActiveModel {
+        event_id: Set(event.0.id),
+        address: Set(server.address),
+        password: Set(request.password),
+        ..Default::default()
+    }.insert(db).await;
+
+    match result {
+        Ok(server) => {
+            log::info!("Admin: added RCON server {} to event {}: {}", server.id, server.event_id, server.address);
+            HttpResponse::Created().json(RconServerInfo::from(server))
+        }
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            status::err_conflict("RCON server is already added to the event")
+        }
+        Err(e) => {
+            log::error!("Error creating RCON server: {}", e);
+            status::err_server("Error creating RCON server")
+        }
+    }
+}
+
+#[delete("/rcon/{id}")]
+pub(crate) async fn delete_rcon_server(info: web::Path<Uuid>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let id = info.into_inner();
+
+    let result = RconServer::find_by_id(id)
+        .filter(rcon_server::Column::EventId.eq(event.0.id))
+        .one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting RCON server from DB: {}", e);
+        return status::err_server("Error getting RCON server from DB");
+    }
+
+    let server = match result.unwrap() {
+        Some(server) => server,
+        None => return status::err_not_found(),
+    };
+
+    log::info!("Admin: deleting RCON server {}: {}", server.id, server.address);
+    match server.delete(db).await {
+        Ok(_) => status::success(),
+        Err(e) => {
+            log::error!("Error deleting RCON server: {}", e);
+            status::err_server("Error deleting RCON server")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateRconServerRequest {
+    pub address: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+struct GetRconServersResponse {
+    pub data: Vec<RconServerInfo>,
+}
+
+/// The password is never returned.
+#[derive(Serialize)]
+struct RconServerInfo {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub address: String,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<rcon_server::Model> for RconServerInfo {
+    fn from(server: rcon_server::Model) -> Self {
+        Self {
+            id: server.id,
+            event_id: server.event_id,
+            address: server.address,
+            created_at: server.created_at,
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use actix_web::{get, HttpResponse, web};
+use actix_web::web::Data;
+use crate::auth::ApiKeyIdentity;
+use crate::discord::members::MemberCache;
+use crate::status;
+
+/// Reports how effective the guild member cache is. The cache is shared by all events.
+#[get("/stats/members")]
+pub(crate) async fn get_member_cache_stats(members: Data<MemberCache>, identity: web::ReqData<ApiKeyIdentity>) -> HttpResponse {
+    if identity.event_id.is_some() {
+        return status::err_forbidden("Stats of all events can only be read with a key that is not bound to an event");
+    }
+    HttpResponse::Ok().json(members.stats().await)
+}
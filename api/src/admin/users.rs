@@ -0,0 +1,395 @@
+use actix_web::{delete, patch, post, put, HttpResponse, web};
+use actix_web::web::Data;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, QueryFilter, SqlErr, TransactionError, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::event;
+use entity::link_history::LinkAction;
+use entity::prelude::User;
+use entity::user;
+use entity::user::Edition;
+use rusty_interaction::types::Snowflake;
+use crate::{events, history, links, status};
+use crate::discord::members::MemberCache;
+use crate::events::CurrentEvent;
+use crate::mojang::{bedrock, Profiles};
+use crate::names::NameRefresh;
+use crate::rcon::Rcon;
+
+/// Links another account to a Discord user, regardless of the event's account limit.
+#[post("/users")]
+pub(crate) async fn create_user(body: web::Json<CreateUserRequest>, data: Data<DatabaseConnection>, members: Data<MemberCache>, profiles: Data<Profiles>, name_refresh: Data<NameRefresh>, rcon: Data<Rcon>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let event = event.0;
+    let event_id = event.id;
+    let request = body.into_inner();
+
+    let existing = User::find()
+        .filter(user::Column::EventId.eq(event_id))
+        .filter(user::Column::MinecraftUuid.eq(request.uuid))
+        .one(db).await;
+    match existing {
+        Err(e) => {
+            log::error!("Error getting user from DB: {}", e);
+            return status::err_server("Error getting user from DB");
+        }
+        Ok(Some(_)) => {
+            return status::err_conflict("Minecraft account is already linked");
+        }
+        Ok(None) => {}
+    }
+
+    let operator = match is_operator(&members, &event, request.snowflake).await {
+        Ok(operator) => operator,
+        Err(response) => return response,
+    };
+    let name = account_name(&profiles, request.uuid, request.name).await;
+    let user = user::ActiveModel {
+        event_id: Set(event_id),
+        discord_snowflake: Set(request.snowflake as i64),
+        minecraft_uuid: Set(request.uuid),
+        minecraft_name: Set(name),
+        edition: Set(bedrock::edition(request.uuid)),
+        ..Default::default()
+    };
+
+    log::info!("Admin: creating whitelist entry for user {}: {}", request.snowflake, request.uuid);
+    let result = db.transaction::<_, user::Model, DbErr>(|txn| {
+        Box::pin(async move {
+            let user = user.insert(txn).await?;
+            history::record(txn, event_id, LinkAction::Insert, request.snowflake, None, None, Some(request.uuid)).await?;
+            Ok(user)
+        })
+    }).await;
+
+    match result {
+        Ok(user) => {
+            fill_in_name(&name_refresh, &user);
+            rcon.push_change(&event, None, Some(&user), operator).await;
+            HttpResponse::Created().json(AdminUser::from(user))
+        }
+        Err(TransactionError::Transaction(e)) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            status::err_conflict("Minecraft account is already linked")
+        }
+        Err(e) => {
+            log::error!("Error creating user: {}", e);
+            status::err_server("Error creating user")
+        }
+    }
+}
+
+/// Replaces the account of a Discord user, who must not have several accounts linked.
+#[patch("/users/{snowflake}")]
+pub(crate) async fn update_user(info: web::Path<Snowflake>, body: web::Json<LinkRequest>, data: Data<DatabaseConnection>, members: Data<MemberCache>, profiles: Data<Profiles>, name_refresh: Data<NameRefresh>, rcon: Data<Rcon>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let event = event.0;
+    let event_id = event.id;
+    let snowflake = info.into_inner();
+    let request = body.into_inner();
+
+    let result = links::find_by_snowflake(db, event_id, snowflake).await;
+    if let Err(e) = result {
+        log::error!("Error getting user from DB: {}", e);
+        return status::err_server("Error getting user from DB");
+    }
+
+    let mut accounts = result.unwrap();
+    if accounts.is_empty() {
+        return status::err_not_found();
+    }
+    if accounts.len() > 1 {
+        return status::err_conflict("Discord user has several accounts linked, remove the one to replace instead");
+    }
+    let user = accounts.remove(0);
+
+    let conflict = User::find().filter(user::Column::EventId.eq(event_id)).filter(user::Column::MinecraftUuid.eq(request.uuid)).one(db).await;
+    match conflict {
+        Err(e) => {
+            log::error!("Error getting user from DB: {}", e);
+            return status::err_server("Error getting user from DB");
+        }
+        Ok(Some(other)) if other.id != user.id => {
+            return status::err_conflict("Minecraft account is already linked to another Discord user");
+        }
+        Ok(_) => {}
+    }
+
+    let operator = match is_operator(&members, &event, snowflake).await {
+        Ok(operator) => operator,
+        Err(response) => return response,
+    };
+
+    log::info!("Admin: updating whitelist entry for user {}: {}", snowflake, request.uuid);
+    let old = user.clone();
+    let old_uuid = user.minecraft_uuid;
+    let mut user: user::ActiveModel = user.into();
+    user.minecraft_uuid = Set(request.uuid);
+    match request.name {
+        Some(name) => user.minecraft_name = Set(Some(name)),
+        // the stored name belongs to the replaced account
+        None if old_uuid != request.uuid => user.minecraft_name = Set(account_name(&profiles, request.uuid, None).await),
+        None => {}
+    }
+    user.edition = Set(bedrock::edition(request.uuid));
+    user.updated_at = Set(Utc::now().into());
+
+    let result = db.transaction::<_, user::Model, DbErr>(|txn| {
+        Box::pin(async move {
+            links::lock_links(txn, event_id, snowflake).await?;
+            let user = user.update(txn).await?;
+            history::record(txn, event_id, LinkAction::Update, snowflake, None, Some(old_uuid), Some(request.uuid)).await?;
+            Ok(user)
+        })
+    }).await;
+
+    match result {
+        Ok(user) => {
+            fill_in_name(&name_refresh, &user);
+            rcon.push_change(&event, Some(&old), Some(&user), operator).await;
+            HttpResponse::Ok().json(AdminUser::from(user))
+        }
+        Err(TransactionError::Transaction(e)) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            status::err_conflict("Minecraft account is already linked to another Discord user")
+        }
+        Err(e) => {
+            log::error!("Error updating user: {}", e);
+            status::err_server("Error updating user")
+        }
+    }
+}
+
+/// Removes all accounts of a Discord user, or only the one given as `uuid`.
+#[delete("/users/{snowflake}")]
+pub(crate) async fn delete_user(info: web::Path<Snowflake>, query: web::Query<DeleteUserQuery>, data: Data<DatabaseConnection>, members: Data<MemberCache>, rcon: Data<Rcon>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let event = event.0;
+    let event_id = event.id;
+    let snowflake = info.into_inner();
+    let uuid = query.uuid;
+
+    let result = links::find_by_snowflake(db, event_id, snowflake).await;
+    if let Err(e) = result {
+        log::error!("Error getting user from DB: {}", e);
+        return status::err_server("Error getting user from DB");
+    }
+
+    let accounts: Vec<user::Model> = result.unwrap().into_iter()
+        .filter(|user| uuid.map_or(true, |uuid| user.minecraft_uuid == uuid))
+        .collect();
+    if accounts.is_empty() {
+        return status::err_not_found();
+    }
+
+    let operator = match is_operator(&members, &event, snowflake).await {
+        Ok(operator) => operator,
+        Err(response) => return response,
+    };
+
+    log::info!("Admin: removing whitelist entries for user {}", snowflake);
+    let removed = accounts.clone();
+    let result = db.transaction::<_, (), DbErr>(|txn| {
+        Box::pin(async move {
+            for user in accounts {
+                let old_uuid = user.minecraft_uuid;
+                user.delete(txn).await?;
+                history::record(txn, event_id, LinkAction::Remove, snowflake, None, Some(old_uuid), None).await?;
+            }
+            Ok(())
+        })
+    }).await;
+
+    match result {
+        Ok(_) => {
+            for user in &removed {
+                rcon.push_change(&event, Some(user), None, operator).await;
+            }
+            status::success()
+        }
+        Err(e) => {
+            log::error!("Error deleting user: {}", e);
+            status::err_server("Error deleting user")
+        }
+    }
+}
+
+/// Links a Discord user to a Minecraft account, taking it from whoever else linked it. The user's
+/// other accounts are kept, unless they are at their account limit, in which case the oldest one
+/// is replaced.
+#[put("/users/{snowflake}/link")]
+pub(crate) async fn force_link(info: web::Path<Snowflake>, body: web::Json<LinkRequest>, data: Data<DatabaseConnection>, members: Data<MemberCache>, profiles: Data<Profiles>, name_refresh: Data<NameRefresh>, rcon: Data<Rcon>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let event = event.0;
+    let event_id = event.id;
+    let snowflake = info.into_inner();
+    let request = body.into_inner();
+    let uuid = request.uuid;
+    let name = account_name(&profiles, uuid, request.name).await;
+
+    let (limit, operator) = match members.get(event.guild_id as Snowflake, snowflake).await {
+        Ok(roles) => {
+            let roles = roles.unwrap_or_default();
+            (events::max_accounts(&event, &roles), events::is_moderator(&event, &roles))
+        }
+        Err(e) => {
+            log::error!("Error getting user from Discord: {}", e);
+            return status::err_server("Error getting user from Discord");
+        }
+    };
+
+    log::info!("Admin: force-linking user {} to {}", snowflake, uuid);
+    // returns the links taken from other users, the replaced link and the new one
+    let result = db.transaction::<_, (Vec<user::Model>, Option<user::Model>, user::Model), DbErr>(|txn| {
+        Box::pin(async move {
+            links::lock_links(txn, event_id, snowflake).await?;
+
+            let others = User::find()
+                .filter(user::Column::EventId.eq(event_id))
+                .filter(user::Column::MinecraftUuid.eq(uuid))
+                .filter(user::Column::DiscordSnowflake.ne(snowflake as i64))
+                .all(txn).await?;
+            for other in &others {
+                let other_snowflake = other.discord_snowflake as Snowflake;
+                other.clone().delete(txn).await?;
+                history::record(txn, event_id, LinkAction::Remove, other_snowflake, None, Some(uuid), None).await?;
+            }
+
+            let accounts = links::find_by_snowflake(txn, event_id, snowflake).await?;
+            if let Some(existing) = accounts.iter().find(|account| account.minecraft_uuid == uuid) {
+                let mut user: user::ActiveModel = existing.clone().into();
+                if let Some(name) = name {
+                    user.minecraft_name = Set(Some(name));
+                }
+                user.updated_at = Set(Utc::now().into());
+                return Ok((others, Some(existing.clone()), user.update(txn).await?));
+            }
+
+            // accounts are oldest first
+            match accounts.first() {
+                Some(oldest) if accounts.len() as u32 >= limit => {
+                    let old_uuid = oldest.minecraft_uuid;
+                    let mut user: user::ActiveModel = oldest.clone().into();
+                    user.minecraft_uuid = Set(uuid);
+                    user.minecraft_name = Set(name);
+                    user.edition = Set(bedrock::edition(uuid));
+                    user.updated_at = Set(Utc::now().into());
+                    let user = user.update(txn).await?;
+                    history::record(txn, event_id, LinkAction::Update, snowflake, None, Some(old_uuid), Some(uuid)).await?;
+                    Ok((others, Some(oldest.clone()), user))
+                }
+                _ => {
+                    let user = user::ActiveModel {
+                        event_id: Set(event_id),
+                        discord_snowflake: Set(snowflake as i64),
+                        minecraft_uuid: Set(uuid),
+                        minecraft_name: Set(name),
+                        edition: Set(bedrock::edition(uuid)),
+                        ..Default::default()
+                    }.insert(txn).await?;
+                    history::record(txn, event_id, LinkAction::Insert, snowflake, None, None, Some(uuid)).await?;
+                    Ok((others, None, user))
+                }
+            }
+        })
+    }).await;
+
+    match result {
+        Ok((others, old, user)) => {
+            for other in &others {
+                // revoking operator permissions of a player without them does no harm
+                let other_operator = is_operator(&members, &event, other.discord_snowflake as Snowflake).await.unwrap_or(true);
+                rcon.push_change(&event, Some(other), None, other_operator).await;
+            }
+            fill_in_name(&name_refresh, &user);
+            rcon.push_change(&event, old.as_ref(), Some(&user), operator).await;
+            HttpResponse::Ok().json(AdminUser::from(user))
+        }
+        Err(e) => {
+            log::error!("Error linking user: {}", e);
+            status::err_server("Error linking user")
+        }
+    }
+}
+
+/// Whether a Discord user is a moderator of the event, whose accounts are operators.
+async fn is_operator(members: &MemberCache, event: &event::Model, snowflake: Snowflake) -> Result<bool, HttpResponse> {
+    match members.get(event.guild_id as Snowflake, snowflake).await {
+        Ok(roles) => Ok(events::is_moderator(event, &roles.unwrap_or_default())),
+        Err(e) => {
+            log::error!("Error getting user from Discord: {}", e);
+            Err(status::err_server("Error getting user from Discord"))
+        }
+    }
+}
+
+/// The name to store for a linked account: the given one, or else the current one of the Java
+/// account, as the exports leave out accounts without a name.
+async fn account_name(profiles: &Profiles, uuid: Uuid, name: Option<String>) -> Option<String> {
+    if name.is_some() || bedrock::edition(uuid) == Edition::Bedrock {
+        return name;
+    }
+
+    match profiles.resolve_uuid(uuid).await {
+        Ok(profile) => profile.map(|profile| profile.name),
+        Err(e) => {
+            log::warn!("Failed to resolve profile of {}: {}", uuid, e);
+            None
+        }
+    }
+}
+
+/// Looks up the name of a link stored without one in the background.
+fn fill_in_name(name_refresh: &NameRefresh, user: &user::Model) {
+    if user.minecraft_name.is_none() && user.edition == Edition::Java {
+        name_refresh.trigger_unnamed();
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateUserRequest {
+    pub snowflake: Snowflake,
+    pub uuid: Uuid,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DeleteUserQuery {
+    pub uuid: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LinkRequest {
+    pub uuid: Uuid,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AdminUser {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub snowflake: Snowflake,
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    pub edition: Edition,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+impl From<user::Model> for AdminUser {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id,
+            event_id: user.event_id,
+            snowflake: user.discord_snowflake as Snowflake,
+            uuid: user.minecraft_uuid,
+            name: user.minecraft_name,
+            edition: user.edition,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
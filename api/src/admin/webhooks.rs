@@ -0,0 +1,136 @@
+use actix_web::{delete, get, post, HttpResponse, web};
+use actix_web::web::Data;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use entity::webhook_subscription;
+use entity::prelude::WebhookSubscription;
+use crate::{status, webhooks};
+use crate::events::CurrentEvent;
+
+#[get("/webhooks")]
+pub(crate) async fn get_webhooks(data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+
+    let result = WebhookSubscription::find()
+        .filter(webhook_subscription::Column::EventId.eq(event.0.id))
+        .order_by_asc(webhook_subscription::Column::CreatedAt)
+        .all(db).await;
+
+    match result {
+        Ok(subscriptions) => HttpResponse::Ok().json(GetWebhooksResponse {
+            data: subscriptions.into_iter().map(WebhookInfo::from).collect(),
+        }),
+        Err(e) => {
+            log::error!("Error getting webhooks from DB: {}", e);
+            status::err_server("Error getting webhooks from DB")
+        }
+    }
+}
+
+/// Subscribes a URL to the link changes of the event. The signing secret is generated unless one
+/// is given, and only ever returned in this response.
+#[post("/webhooks")]
+pub(crate) async fn create_webhook(body: web::Json<CreateWebhookRequest>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let request = body.into_inner();
+
+    match reqwest::Url::parse(&request.url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => return status::err_bad_request("url must be an http or https URL"),
+    }
+    if request.secret.as_ref().is_some_and(|secret| secret.is_empty()) {
+        return status::err_bad_request("secret must not be empty");
+    }
+
+    let secret = request.secret.unwrap_or_else(webhooks::generate_secret);
+    let result = webhook_subscription::ActiveModel {
+        event_id: Set(event.0.id),
+        url: Set(request.url),
+        secret: Set(secret.clone()),
+        ..Default::default()
+    }.insert(db).await;
+
+    match result {
+        Ok(subscription) => {
+            log::info!("Admin: subscribed webhook {} to event {}: {}", subscription.id, subscription.event_id, subscription.url);
+            HttpResponse::Created().json(CreateWebhookResponse {
+                secret,
+                info: WebhookInfo::from(subscription),
+            })
+        }
+        Err(e) => {
+            log::error!("Error creating webhook: {}", e);
+            status::err_server("Error creating webhook")
+        }
+    }
+}
+
+/// Deletes a subscription together with its pending deliveries.
+#[delete("/webhooks/{id}")]
+pub(crate) async fn delete_webhook(info: web::Path<Uuid>, data: Data<DatabaseConnection>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let id = info.into_inner();
+
+    let result = WebhookSubscription::find_by_id(id)
+        .filter(webhook_subscription::Column::EventId.eq(event.0.id))
+        .one(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting webhook from DB: {}", e);
+        return status::err_server("Error getting webhook from DB");
+    }
+
+    let subscription = match result.unwrap() {
+        Some(subscription) => subscription,
+        None => return status::err_not_found(),
+    };
+
+    log::info!("Admin: deleting webhook {}: {}", subscription.id, subscription.url);
+    match subscription.delete(db).await {
+        Ok(_) => status::success(),
+        Err(e) => {
+            log::error!("Error deleting webhook: {}", e);
+            status::err_server("Error deleting webhook")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreateWebhookResponse {
+    pub secret: String,
+    #[serde(flatten)]
+    pub info: WebhookInfo,
+}
+
+#[derive(Serialize)]
+struct GetWebhooksResponse {
+    pub data: Vec<WebhookInfo>,
+}
+
+#[derive(Serialize)]
+struct WebhookInfo {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub url: String,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<webhook_subscription::Model> for WebhookInfo {
+    fn from(subscription: webhook_subscription::Model) -> Self {
+        Self {
+            id: subscription.id,
+            event_id: subscription.event_id,
+            url: subscription.url,
+            created_at: subscription.created_at,
+        }
+    }
+}
@@ -1,51 +1,272 @@
-use actix_web::{get, HttpResponse, web};
+use actix_web::{get, HttpResponse, post, web};
 use actix_web::web::Data;
-use anyhow::Context;
-use reqwest::{Client, header, StatusCode};
-use sea_orm::{DatabaseConnection, EntityTrait, ColumnTrait, QueryFilter};
+use sea_orm::{Condition, DatabaseConnection, DbErr, EntityTrait, ColumnTrait, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select};
+use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
 use serde_with::*;
 use uuid::Uuid;
 use entity::prelude::User;
-use entity::user;
+use entity::{event, user};
+use entity::user::Edition;
 use rusty_interaction::types::Snowflake;
-use crate::{discord, status};
+use crate::{events, status};
+use crate::config::Config;
+use crate::discord::members::MemberCache;
+use crate::events::CurrentEvent;
 
-const BASE_URL: &str = rusty_interaction::BASE_URL;
+pub(crate) mod stream;
 
+/// Permission level granted to operators in the generated `ops.json`.
+const OP_LEVEL: u8 = 4;
+
+const MAX_LIMIT: u64 = 1000;
+
+/// Lists users, optionally filtered and paginated. Discord users with several linked accounts have
+/// an entry for each of them.
+///
+/// Without a `limit` all matching users are returned. Filters on `access` and `operator` depend on
+/// Discord data, so those are applied after loading the matching rows from the database.
 #[get("/users")]
-pub(crate) async fn get_users(data: Data<DatabaseConnection>, client: Data<Client>) -> HttpResponse {
+pub(crate) async fn get_users(query: web::Query<UsersQuery>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
     let db = data.get_ref();
-    let client = client.get_ref();
+    let members = members.get_ref();
+    let event = &event.0;
+    let offline_uuids = config.profiles.offline_uuids;
+    let query = query.into_inner();
 
-    let result= User::find().all(db).await;
-    if let Err(e) = result {
-        log::error!("Error getting users from DB: {}", e);
-        return status::err_server("Error getting users from DB");
+    let mut select = User::find().filter(user::Column::EventId.eq(event.id));
+    if let Some(snowflakes) = &query.snowflake {
+        let snowflakes: Result<Vec<i64>, _> = split_list(snowflakes).map(|s| s.parse::<Snowflake>().map(|s| s as i64)).collect();
+        match snowflakes {
+            Ok(snowflakes) => select = select.filter(user::Column::DiscordSnowflake.is_in(snowflakes)),
+            Err(_) => return status::err_bad_request("snowflake must be a comma separated list of Snowflakes"),
+        }
     }
-
-    let mut users: Vec<UserData> = Vec::new();
-    for u in result.unwrap() {
-        let user_data = get_user_data(u.discord_snowflake as Snowflake, u.minecraft_uuid, client).await;
-        if let Err(e) = user_data {
-            log::error!("Error getting user from Discord: {}", e);
-            return status::err_server("Error getting user from Discord");
+    if let Some(uuids) = &query.uuid {
+        let uuids: Result<Vec<Uuid>, _> = split_list(uuids).map(Uuid::parse_str).collect();
+        match uuids {
+            Ok(uuids) => select = select.filter(user::Column::MinecraftUuid.is_in(uuids)),
+            Err(_) => return status::err_bad_request("uuid must be a comma separated list of UUIDs"),
         }
-        users.push(user_data.unwrap());
     }
 
+    let order = match query.order.unwrap_or_default() {
+        SortOrder::Asc => Order::Asc,
+        SortOrder::Desc => Order::Desc,
+    };
+    let column = match query.sort.unwrap_or_default() {
+        SortField::CreatedAt => user::Column::CreatedAt,
+        SortField::UpdatedAt => user::Column::UpdatedAt,
+        SortField::Name => user::Column::MinecraftName,
+        SortField::Snowflake => user::Column::DiscordSnowflake,
+        SortField::Uuid => user::Column::MinecraftUuid,
+    };
+    // the id keeps the order stable between pages when the sort column has duplicates
+    select = select.order_by(column, order.clone()).order_by(user::Column::Id, order);
+
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.map(|limit| limit.min(MAX_LIMIT));
+
+    let (users, total) = if query.access.is_none() && query.operator.is_none() {
+        let total = match select.clone().count(db).await {
+            Ok(total) => total,
+            Err(e) => {
+                log::error!("Error counting users in DB: {}", e);
+                return status::err_server("Error getting users from DB");
+            }
+        };
+
+        let users = match fetch_users(db, select.offset(offset).limit(limit), members, event, offline_uuids).await {
+            Ok(users) => users,
+            Err(response) => return response,
+        };
+        (users, total)
+    } else {
+        let users = match fetch_users(db, select, members, event, offline_uuids).await {
+            Ok(users) => users,
+            Err(response) => return response,
+        };
+
+        let users: Vec<(user::Model, UserData)> = users.into_iter()
+            .filter(|(_, user_data)| query.access.map_or(true, |access| user_data.access == access))
+            .filter(|(_, user_data)| query.operator.map_or(true, |operator| user_data.operator == operator))
+            .collect();
+        let total = users.len() as u64;
+
+        let users = users.into_iter()
+            .skip(offset as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect();
+        (users, total)
+    };
+
     return HttpResponse::Ok().json(GetUsersResponse {
-        data: Some(users)
+        data: Some(users.into_iter().map(|(_, user_data)| user_data).collect()),
+        pagination: Pagination {
+            offset,
+            limit,
+            total,
+        },
     });
 }
 
+/// Renders all users with access in the vanilla `whitelist.json` format. With `edition=true` each
+/// entry also has the edition of the account, for tools that need to tell them apart.
+///
+/// Servers in offline mode get the offline UUIDs of Java players with `online_mode=false`.
+#[get("/whitelist.json")]
+pub(crate) async fn get_whitelist(query: web::Query<ExportQuery>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let members = members.get_ref();
+    let event = &event.0;
+    let online_mode = query.online_mode.unwrap_or(true);
+    let with_edition = query.edition.unwrap_or(false);
+    if !online_mode && !config.profiles.offline_uuids {
+        return status::err_bad_request("Offline UUIDs are not enabled");
+    }
+
+    let users = match fetch_users(db, User::find().filter(user::Column::EventId.eq(event.id)), members, event, false).await {
+        Ok(users) => users,
+        Err(response) => return response,
+    };
+
+    let users = users.into_iter().filter(|(_, user_data)| user_data.access);
+    let entries: Vec<WhitelistEntry> = with_names(users).into_iter()
+        .map(|(user, name)| WhitelistEntry {
+            uuid: export_uuid(&user, online_mode),
+            name,
+            edition: with_edition.then_some(user.edition),
+        })
+        .collect();
+
+    return HttpResponse::Ok().json(entries);
+}
+
+/// Renders all users with operator permissions in the vanilla `ops.json` format, see [`get_whitelist`]
+/// for offline mode and editions.
+#[get("/ops.json")]
+pub(crate) async fn get_ops(query: web::Query<ExportQuery>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let members = members.get_ref();
+    let event = &event.0;
+    let online_mode = query.online_mode.unwrap_or(true);
+    let with_edition = query.edition.unwrap_or(false);
+    if !online_mode && !config.profiles.offline_uuids {
+        return status::err_bad_request("Offline UUIDs are not enabled");
+    }
+
+    let users = match fetch_users(db, User::find().filter(user::Column::EventId.eq(event.id)), members, event, false).await {
+        Ok(users) => users,
+        Err(response) => return response,
+    };
+
+    let users = users.into_iter().filter(|(_, user_data)| user_data.access && user_data.operator);
+    let entries: Vec<OpsEntry> = with_names(users).into_iter()
+        .map(|(user, name)| OpsEntry {
+            uuid: export_uuid(&user, online_mode),
+            name,
+            edition: with_edition.then_some(user.edition),
+            level: OP_LEVEL,
+            bypasses_player_limit: false,
+        })
+        .collect();
+
+    return HttpResponse::Ok().json(entries);
+}
+
+/// Finds a user by the UUID of their Minecraft account, or its offline UUID if those are enabled.
 #[get("/users/{uuid}")]
-pub(crate) async fn get_user(info: web::Path<Uuid>, data: Data<DatabaseConnection>, client: Data<Client>) -> HttpResponse {
+pub(crate) async fn get_user(info: web::Path<Uuid>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
     let db = data.get_ref();
-    let client = client.get_ref();
     let uuid = info.into_inner();
+    let offline_uuids = config.profiles.offline_uuids;
+
+    let mut condition = Condition::any().add(user::Column::MinecraftUuid.eq(uuid));
+    if offline_uuids {
+        condition = condition.add(user::Column::OfflineUuid.eq(uuid));
+    }
+
+    // offline UUIDs are version 3 and can't collide with the version 4 UUIDs of online accounts
+    let result = User::find().filter(user::Column::EventId.eq(event.0.id)).filter(condition).one(db).await;
+    user_response(result, members.get_ref(), &event.0, offline_uuids).await
+}
+
+/// Finds a user by Discord snowflake. Of several linked accounts the oldest is returned, see
+/// [`get_user_accounts_by_discord`] for all of them.
+#[get("/users/by-discord/{snowflake}")]
+pub(crate) async fn get_user_by_discord(info: web::Path<Snowflake>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let snowflake = info.into_inner();
+
+    let result = User::find()
+        .filter(user::Column::EventId.eq(event.0.id))
+        .filter(user::Column::DiscordSnowflake.eq(snowflake as i64))
+        .order_by_asc(user::Column::CreatedAt)
+        .one(db).await;
+    user_response(result, members.get_ref(), &event.0, config.profiles.offline_uuids).await
+}
+
+/// Lists all accounts a Discord user linked, oldest first.
+#[get("/users/by-discord/{snowflake}/accounts")]
+pub(crate) async fn get_user_accounts_by_discord(info: web::Path<Snowflake>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let snowflake = info.into_inner();
+    let event = &event.0;
+
+    let select = User::find()
+        .filter(user::Column::EventId.eq(event.id))
+        .filter(user::Column::DiscordSnowflake.eq(snowflake as i64))
+        .order_by_asc(user::Column::CreatedAt);
+    let users = match fetch_users(db, select, members.get_ref(), event, config.profiles.offline_uuids).await {
+        Ok(users) => users,
+        Err(response) => return response,
+    };
+
+    if users.is_empty() {
+        return status::err_not_found();
+    }
+
+    HttpResponse::Ok().json(LookupResponse {
+        data: users.into_iter().map(|(_, user_data)| user_data).collect(),
+    })
+}
+
+/// Looks up multiple users at once by Minecraft UUID and/or Discord snowflake.
+///
+/// Only linked users are returned, callers can match them up through the `uuid` and `snowflake`
+/// fields of each entry.
+#[post("/users/lookup")]
+pub(crate) async fn lookup_users(body: web::Json<LookupRequest>, data: Data<DatabaseConnection>, members: Data<MemberCache>, config: Data<Config>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref();
+    let members = members.get_ref();
+    let event = &event.0;
+    let request = body.into_inner();
+
+    if request.uuids.len() + request.snowflakes.len() > MAX_LIMIT as usize {
+        return status::err_bad_request(&format!("Cannot look up more than {MAX_LIMIT} users at once"));
+    }
+
+    if request.uuids.is_empty() && request.snowflakes.is_empty() {
+        return HttpResponse::Ok().json(LookupResponse {
+            data: Vec::new(),
+        });
+    }
+
+    let condition = Condition::any()
+        .add(user::Column::MinecraftUuid.is_in(request.uuids))
+        .add(user::Column::DiscordSnowflake.is_in(request.snowflakes.into_iter().map(|snowflake| snowflake as i64)));
+
+    let users = match fetch_users(db, User::find().filter(user::Column::EventId.eq(event.id)).filter(condition).order_by_asc(user::Column::Id), members, event, config.profiles.offline_uuids).await {
+        Ok(users) => users,
+        Err(response) => return response,
+    };
+
+    return HttpResponse::Ok().json(LookupResponse {
+        data: users.into_iter().map(|(_, user_data)| user_data).collect(),
+    });
+}
 
-    let result = User::find().filter(user::Column::MinecraftUuid.eq(uuid)).one(db).await;
+async fn user_response(result: Result<Option<user::Model>, DbErr>, members: &MemberCache, event: &event::Model, offline_uuids: bool) -> HttpResponse {
     if let Err(e) = result {
         log::error!("Error getting user from DB: {}", e);
         return status::err_server("Error getting user from DB");
@@ -57,7 +278,7 @@ pub(crate) async fn get_user(info: web::Path<Uuid>, data: Data<DatabaseConnectio
     }
 
     let user = user.unwrap();
-    let user_data = get_user_data(user.discord_snowflake as Snowflake, user.minecraft_uuid, client).await;
+    let user_data = get_user_data(&user, members, event, offline_uuids).await;
     if let Err(e) = user_data {
         log::error!("Error getting user from Discord: {}", e);
         return status::err_server("Error getting user from Discord");
@@ -66,52 +287,180 @@ pub(crate) async fn get_user(info: web::Path<Uuid>, data: Data<DatabaseConnectio
     return HttpResponse::Ok().json(user_data.unwrap());
 }
 
-async fn get_user_data(snowflake: Snowflake, uuid: Uuid, client: &Client) -> anyhow::Result<UserData> {
-    let moderators = unsafe { &discord::MODERATOR_ROLES };
-    let guild_id = unsafe { discord::GUILD_ID };
-
-    let response = client.get(format!("{BASE_URL}/guilds/{guild_id}/members/{snowflake}")).header(header::ACCEPT, "application/json").send().await
-        .context("Failed to get discord user info")?;
-    if !response.status().is_success() {
+async fn fetch_users(db: &DatabaseConnection, select: Select<User>, members: &MemberCache, event: &event::Model, offline_uuids: bool) -> Result<Vec<(user::Model, UserData)>, HttpResponse> {
+    let result = select.all(db).await;
+    if let Err(e) = result {
+        log::error!("Error getting users from DB: {}", e);
+        return Err(status::err_server("Error getting users from DB"));
+    }
 
-        if response.status() != StatusCode::NOT_FOUND {
-            anyhow::bail!("Error getting user from Discord - {}: {}", response.status(), response.text().await?);
+    let mut users: Vec<(user::Model, UserData)> = Vec::new();
+    for u in result.unwrap() {
+        let user_data = get_user_data(&u, members, event, offline_uuids).await;
+        if let Err(e) = user_data {
+            log::error!("Error getting user from Discord: {}", e);
+            return Err(status::err_server("Error getting user from Discord"));
         }
-
-        return Ok(UserData {
-            uuid,
-            snowflake,
-            ..Default::default()
-        });
+        users.push((u, user_data.unwrap()));
     }
 
-    let member = response.json::<GuildMember>().await.context("unable to parse guild member json response")?;
-    let operator = member.roles.iter().map(|s| s.parse::<Snowflake>().unwrap()).any(|r| moderators.contains(&r));
+    Ok(users)
+}
+
+async fn get_user_data(user: &user::Model, members: &MemberCache, event: &event::Model, offline_uuids: bool) -> anyhow::Result<UserData> {
+    let snowflake = user.discord_snowflake as Snowflake;
+
+    let (access, operator) = match members.get(event.guild_id as Snowflake, snowflake).await? {
+        Some(roles) => (true, events::is_moderator(event, &roles)),
+        None => (false, false),
+    };
 
     Ok(UserData {
-        access: true,
+        access,
         operator,
-        uuid,
-        snowflake
+        uuid: user.minecraft_uuid,
+        offline_uuid: user.offline_uuid.filter(|_| offline_uuids),
+        snowflake,
+        name: user.minecraft_name.clone(),
+        edition: user.edition,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
     })
 }
 
-#[derive(Deserialize)]
-struct GuildMember {
-    roles: Vec<String>
-}
-
-#[derive(Serialize, Default)]
+#[derive(Serialize)]
 struct UserData {
     pub access: bool,
     #[serde(default)]
     pub operator: bool,
     pub uuid: Uuid,
-    pub snowflake: Snowflake
+    /// Only with offline UUIDs enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_uuid: Option<Uuid>,
+    pub snowflake: Snowflake,
+    pub name: Option<String>,
+    pub edition: Edition,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+/// Pairs the users with their Minecraft names, which the vanilla formats require. Links made before
+/// names were stored have none until the name refresh filled them in, so those are left out.
+fn with_names(users: impl Iterator<Item = (user::Model, UserData)>) -> Vec<(user::Model, String)> {
+    let mut unnamed = 0;
+    let users = users
+        .filter_map(|(user, _)| match user.minecraft_name.clone() {
+            Some(name) => Some((user, name)),
+            None => {
+                unnamed += 1;
+                None
+            }
+        })
+        .collect();
+
+    if unnamed > 0 {
+        log::warn!("Leaving {} users without a known Minecraft name out of the export", unnamed);
+    }
+    users
+}
+
+/// Bedrock players keep their Floodgate UUID in offline mode.
+fn export_uuid(user: &user::Model, online_mode: bool) -> Uuid {
+    match user.offline_uuid {
+        Some(offline_uuid) if !online_mode => offline_uuid,
+        _ => user.minecraft_uuid,
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UsersQuery {
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+    pub access: Option<bool>,
+    pub operator: Option<bool>,
+    /// Comma separated list of Discord user IDs.
+    pub snowflake: Option<String>,
+    /// Comma separated list of Minecraft UUIDs.
+    pub uuid: Option<String>,
+    pub sort: Option<SortField>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
+    Snowflake,
+    Uuid,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
 }
 
 #[derive(Serialize)]
 struct GetUsersResponse {
     #[serde(default)]
-    pub data: Option<Vec<UserData>>
+    pub data: Option<Vec<UserData>>,
+    pub pagination: Pagination,
+}
+
+#[derive(Serialize)]
+struct Pagination {
+    pub offset: u64,
+    /// `None` if all remaining users were returned.
+    pub limit: Option<u64>,
+    /// Number of users matching the filters.
+    pub total: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LookupRequest {
+    #[serde(default)]
+    pub uuids: Vec<Uuid>,
+    #[serde(default)]
+    pub snowflakes: Vec<Snowflake>,
+}
+
+#[derive(Serialize)]
+struct LookupResponse {
+    pub data: Vec<UserData>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ExportQuery {
+    /// `false` for servers in offline mode.
+    pub online_mode: Option<bool>,
+    /// `true` to add the edition of each account, which isn't part of the vanilla format.
+    pub edition: Option<bool>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct WhitelistEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    pub edition: Option<Edition>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OpsEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    pub edition: Option<Edition>,
+    pub level: u8,
+    pub bypasses_player_limit: bool,
 }
\ No newline at end of file
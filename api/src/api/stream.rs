@@ -0,0 +1,327 @@
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::web::{Bytes, Data};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+use entity::{event, link_history};
+use entity::link_history::LinkAction;
+use entity::prelude::LinkHistory;
+use rusty_interaction::types::Snowflake;
+use crate::{events, history, status};
+use crate::discord::members::MemberCache;
+use crate::events::CurrentEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the feed waits for a missing history entry before moving on. Ids are taken before the
+/// inserting transaction commits, so an entry can show up after ones with higher ids, or never if
+/// its transaction was rolled back.
+const GAP_TIMEOUT: Duration = Duration::from_secs(30);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const BATCH_SIZE: u64 = 100;
+/// Batches kept for slow streams, which catch up from the database once they fall further behind.
+const FEED_CAPACITY: usize = 64;
+
+type Message = Result<Bytes, actix_web::Error>;
+
+/// New link history entries of all events, polled once and shared by all streams.
+pub(crate) struct HistoryFeed {
+    sender: broadcast::Sender<Arc<Batch>>,
+    /// Every entry up to this id was published or will never be.
+    cursor: AtomicI64,
+}
+
+struct Batch {
+    entries: Vec<link_history::Model>,
+    /// The feed's cursor after this batch.
+    cursor: i64,
+}
+
+impl HistoryFeed {
+    /// Starts polling for entries after the latest one.
+    pub async fn start(db: DatabaseConnection) -> Result<Data<Self>, DbErr> {
+        let latest = LinkHistory::find().order_by_desc(link_history::Column::Id).one(&db).await?;
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let feed = Data::new(Self {
+            sender,
+            cursor: AtomicI64::new(latest.map(|entry| entry.id).unwrap_or(0)),
+        });
+
+        tokio::spawn(poll(feed.clone(), db));
+        Ok(feed)
+    }
+}
+
+/// Streams changes to the links of an event as server-sent events.
+///
+/// Each event's `id` is a cursor: a reconnecting client sends the last one it received as
+/// `Last-Event-ID` header or `cursor` query parameter to catch up on everything it missed.
+/// Changes it already received may be sent again then, clients recognize them by the `id` of the
+/// history entry in the data. Without a cursor only changes made after connecting are sent.
+#[get("/stream")]
+pub(crate) async fn get_stream(req: HttpRequest, query: web::Query<StreamQuery>, data: Data<DatabaseConnection>, feed: Data<HistoryFeed>, members: Data<MemberCache>, event: CurrentEvent) -> HttpResponse {
+    let db = data.get_ref().clone();
+    let event = event.0;
+
+    let cursor = query.cursor.or_else(|| {
+        req.headers().get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    });
+
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => {
+            let latest = LinkHistory::find()
+                .filter(link_history::Column::EventId.eq(event.id))
+                .order_by_desc(link_history::Column::Id)
+                .one(&db).await;
+            match latest {
+                Ok(latest) => latest.map(|entry| entry.id).unwrap_or(0),
+                Err(e) => {
+                    log::error!("Error getting link history from DB: {}", e);
+                    return status::err_server("Error getting link history from DB");
+                }
+            }
+        }
+    };
+
+    let (sender, receiver) = mpsc::channel::<Message>(BATCH_SIZE as usize);
+    actix_web::rt::spawn(run(db, feed, members, event, cursor, sender));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(ReceiverStream::new(receiver))
+}
+
+/// Polls the link history for new entries, remembering the ones past a gap until it is filled or
+/// times out.
+async fn poll(feed: Data<HistoryFeed>, db: DatabaseConnection) {
+    let mut cursor = feed.cursor.load(Ordering::Acquire);
+    // published entries past the gap at `cursor + 1`
+    let mut seen: BTreeSet<i64> = BTreeSet::new();
+    let mut gap: Option<(i64, Instant)> = None;
+
+    loop {
+        let highest = seen.last().copied().unwrap_or(cursor);
+        let result = async {
+            let mut entries = if highest > cursor {
+                LinkHistory::find()
+                    .filter(link_history::Column::Id.gt(cursor))
+                    .filter(link_history::Column::Id.lt(highest))
+                    .filter(link_history::Column::Id.is_not_in(seen.iter().copied()))
+                    .order_by_asc(link_history::Column::Id)
+                    .all(&db).await?
+            } else {
+                Vec::new()
+            };
+            let new = LinkHistory::find()
+                .filter(link_history::Column::Id.gt(highest))
+                .order_by_asc(link_history::Column::Id)
+                .limit(BATCH_SIZE)
+                .all(&db).await?;
+            let full_batch = new.len() as u64 == BATCH_SIZE;
+            entries.extend(new);
+            Ok::<_, DbErr>((entries, full_batch))
+        }.await;
+
+        let (entries, full_batch) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                log::error!("Error getting link history from DB: {}", e);
+                (Vec::new(), false)
+            }
+        };
+        seen.extend(entries.iter().map(|entry| entry.id));
+
+        loop {
+            while seen.first() == Some(&(cursor + 1)) {
+                seen.pop_first();
+                cursor += 1;
+            }
+
+            let next = match seen.first() {
+                Some(&next) => next,
+                None => break,
+            };
+            let since = match gap {
+                Some((at, since)) if at == cursor => since,
+                _ => Instant::now(),
+            };
+            gap = Some((cursor, since));
+            if since.elapsed() < GAP_TIMEOUT {
+                break;
+            }
+
+            log::warn!("Giving up on link history entries {} to {}", cursor + 1, next - 1);
+            cursor = next - 1;
+        }
+
+        // published before the cursor moves, so streams that subscribe after reading the cursor
+        // find these entries in the database instead
+        if !entries.is_empty() {
+            // only fails without any streams
+            let _ = feed.sender.send(Arc::new(Batch {
+                entries,
+                cursor,
+            }));
+        }
+        feed.cursor.store(cursor, Ordering::Release);
+
+        if !full_batch {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Sends the entries after the cursor from the database, then the ones published by the feed,
+/// until the client disconnects.
+async fn run(db: DatabaseConnection, feed: Data<HistoryFeed>, members: Data<MemberCache>, event: event::Model, mut cursor: i64, sender: Sender<Message>) {
+    let mut last_sent = Instant::now();
+
+    loop {
+        // entries up to this id were published before subscribing, later ones may arrive from
+        // both the database and the feed
+        let published = feed.cursor.load(Ordering::Acquire);
+        let mut receiver = feed.sender.subscribe();
+        let mut caught_up: HashSet<i64> = HashSet::new();
+
+        let mut after = cursor;
+        loop {
+            let result = LinkHistory::find()
+                .filter(link_history::Column::EventId.eq(event.id))
+                .filter(link_history::Column::Id.gt(after))
+                .order_by_asc(link_history::Column::Id)
+                .limit(BATCH_SIZE)
+                .all(&db).await;
+
+            let entries = match result {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::error!("Error getting link history from DB: {}", e);
+                    if sender.is_closed() {
+                        return;
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            let full_batch = entries.len() as u64 == BATCH_SIZE;
+
+            for entry in entries {
+                after = entry.id;
+                if entry.id > published {
+                    caught_up.insert(entry.id);
+                }
+                cursor = cursor.max(entry.id.min(published));
+                let message = change_message(entry, cursor, &members, &event).await;
+                if sender.send(Ok(message)).await.is_err() {
+                    return;
+                }
+                last_sent = Instant::now();
+            }
+
+            if !full_batch {
+                break;
+            }
+        }
+
+        loop {
+            let batch = match tokio::time::timeout(KEEP_ALIVE_INTERVAL.saturating_sub(last_sent.elapsed()), receiver.recv()).await {
+                Ok(Ok(batch)) => batch,
+                Ok(Err(RecvError::Lagged(skipped))) => {
+                    log::warn!("Stream of event {} missed {} batches, catching up from the database", event.id, skipped);
+                    break;
+                }
+                Ok(Err(RecvError::Closed)) => return,
+                Err(_) => {
+                    // comments are ignored by clients but let us notice disconnects
+                    if sender.send(Ok(Bytes::from_static(b": keep-alive\n\n"))).await.is_err() {
+                        return;
+                    }
+                    last_sent = Instant::now();
+                    continue;
+                }
+            };
+
+            for entry in batch.entries.iter().filter(|entry| entry.event_id == Some(event.id)) {
+                cursor = cursor.max(entry.id.min(batch.cursor));
+                if caught_up.contains(&entry.id) {
+                    continue;
+                }
+                let message = change_message(entry.clone(), cursor, &members, &event).await;
+                if sender.send(Ok(message)).await.is_err() {
+                    return;
+                }
+                last_sent = Instant::now();
+            }
+            // the feed publishes every entry once, by this batch at the latest
+            caught_up.retain(|&id| id > batch.cursor);
+        }
+    }
+}
+
+/// `cursor` is where the client resumes after reconnecting.
+async fn change_message(entry: link_history::Model, cursor: i64, members: &MemberCache, event: &event::Model) -> Bytes {
+    let snowflake = entry.discord_snowflake as Snowflake;
+
+    let kind = history::change_kind(entry.action);
+
+    // the current state rather than the one at the time of the change, as roles aren't recorded
+    let (access, operator) = match entry.action {
+        LinkAction::Remove => (None, None),
+        _ => match members.get(event.guild_id as Snowflake, snowflake).await {
+            Ok(Some(roles)) => (Some(true), Some(events::is_moderator(event, &roles))),
+            Ok(None) => (Some(false), Some(false)),
+            Err(e) => {
+                log::error!("Error getting user from Discord: {}", e);
+                (None, None)
+            }
+        },
+    };
+
+    let change = Change {
+        id: entry.id,
+        snowflake,
+        uuid: entry.new_uuid.or(entry.old_uuid),
+        old_uuid: entry.old_uuid,
+        new_uuid: entry.new_uuid,
+        actor: entry.actor_snowflake.map(|actor| actor as Snowflake),
+        access,
+        operator,
+        created_at: entry.created_at,
+    };
+
+    let data = serde_json::to_string(&change).expect("Failed to serialize change");
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", cursor, kind, data))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct StreamQuery {
+    pub cursor: Option<i64>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct Change {
+    /// The history entry of the change.
+    pub id: i64,
+    pub snowflake: Snowflake,
+    pub uuid: Option<Uuid>,
+    pub old_uuid: Option<Uuid>,
+    pub new_uuid: Option<Uuid>,
+    pub actor: Option<Snowflake>,
+    pub access: Option<bool>,
+    pub operator: Option<bool>,
+    pub created_at: DateTimeWithTimeZone,
+}
@@ -0,0 +1,242 @@
+use std::fmt::{Display, Formatter};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::web::Data;
+use chrono::Utc;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+use entity::api_key;
+use entity::prelude::ApiKey;
+
+/// `last_used_at` is only written once per interval to avoid a database write on every request.
+const LAST_USED_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ApiScope {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    /// Grants every other scope as well.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl ApiScope {
+    /// The scope needed for a request to the given path below `/api`.
+    pub fn required_for(path: &str) -> Self {
+        if path.starts_with("/api/admin/users") {
+            ApiScope::UsersWrite
+        } else if path.starts_with("/api/admin") {
+            ApiScope::Admin
+        } else {
+            ApiScope::UsersRead
+        }
+    }
+}
+
+impl Display for ApiScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiScope::UsersRead => write!(f, "users:read"),
+            ApiScope::UsersWrite => write!(f, "users:write"),
+            ApiScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "users:read" => Ok(ApiScope::UsersRead),
+            "users:write" => Ok(ApiScope::UsersWrite),
+            "admin" => Ok(ApiScope::Admin),
+            _ => Err(format!("Unknown scope {value:?}")),
+        }
+    }
+}
+
+/// Who made an API request, available from the request extensions once the key is verified.
+#[derive(Clone, Debug)]
+pub(crate) struct ApiKeyIdentity {
+    pub scopes: Vec<ApiScope>,
+    /// The event the key is restricted to, `None` for keys that may access every event.
+    pub event_id: Option<Uuid>,
+}
+
+impl ApiKeyIdentity {
+    pub fn has_scope(&self, required: ApiScope) -> bool {
+        self.scopes.contains(&ApiScope::Admin) || self.scopes.contains(&required)
+    }
+}
+
+/// Scopes are stored space separated.
+pub(crate) fn parse_scopes(scopes: &str) -> Vec<ApiScope> {
+    scopes.split_whitespace().filter_map(|scope| scope.parse().ok()).collect()
+}
+
+pub(crate) fn format_scopes(scopes: &[ApiScope]) -> String {
+    scopes.iter().map(ApiScope::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// Generates a new key for the given key ID, returning the key and the hash to store.
+///
+/// Keys have the form `<id>.<secret>`, so the stored hash can be looked up by ID and compared in
+/// constant time.
+pub(crate) fn generate_key(id: Uuid) -> (String, String) {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let secret: String = secret.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    let key = format!("{}.{}", id.simple(), secret);
+    let hash = hash_key(&key);
+    (key, hash)
+}
+
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Checks a provided key, returning who it belongs to or `None` if it is invalid or expired.
+///
+/// `legacy_key` is the key from the configuration, which grants every scope for every event.
+pub(crate) async fn verify_key(db: &DatabaseConnection, legacy_key: Option<&str>, provided: &str) -> Result<Option<ApiKeyIdentity>, DbErr> {
+    if let Some(legacy_key) = legacy_key.filter(|key| !key.trim().is_empty()) {
+        if bool::from(legacy_key.as_bytes().ct_eq(provided.as_bytes())) {
+            return Ok(Some(ApiKeyIdentity {
+                scopes: vec![ApiScope::Admin],
+                event_id: None,
+            }));
+        }
+    }
+
+    let id = match provided.split_once('.').and_then(|(id, _)| Uuid::parse_str(id).ok()) {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+
+    let key = match ApiKey::find_by_id(id).one(db).await? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    if !bool::from(key.key_hash.as_bytes().ct_eq(hash_key(provided).as_bytes())) {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    if key.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Ok(None);
+    }
+
+    let identity = ApiKeyIdentity {
+        scopes: parse_scopes(&key.scopes),
+        event_id: key.event_id,
+    };
+    let stale = match key.last_used_at {
+        Some(last_used_at) => (now - last_used_at.with_timezone(&Utc)).to_std().is_ok_and(|elapsed| elapsed >= LAST_USED_INTERVAL),
+        None => true,
+    };
+    if stale {
+        let mut key: api_key::ActiveModel = key.into();
+        key.last_used_at = Set(Some(now.into()));
+        key.update(db).await?;
+    }
+
+    Ok(Some(identity))
+}
+
+/// Requires a valid `x-api-key` header with the scope needed for the requested path.
+pub(crate) struct RequireApiKey {
+    legacy_key: Option<String>,
+}
+
+impl RequireApiKey {
+    pub fn new(legacy_key: Option<String>) -> Self {
+        Self {
+            legacy_key,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireApiKey
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireApiKeyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireApiKeyMiddleware {
+            service: Rc::new(service),
+            legacy_key: self.legacy_key.clone(),
+        }))
+    }
+}
+
+pub(crate) struct RequireApiKeyMiddleware<S> {
+    service: Rc<S>,
+    legacy_key: Option<String>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireApiKeyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let legacy_key = self.legacy_key.clone();
+
+        Box::pin(async move {
+            let provided = req.headers().get("x-api-key").and_then(|value| value.to_str().ok()).map(str::to_string);
+            let db = req.app_data::<Data<DatabaseConnection>>().cloned();
+
+            let identity = match (provided, db) {
+                (Some(provided), Some(db)) => match verify_key(db.get_ref(), legacy_key.as_deref(), &provided).await {
+                    Ok(identity) => identity,
+                    Err(e) => {
+                        log::error!("Error verifying API key: {}", e);
+                        return Ok(req.into_response(HttpResponse::InternalServerError().finish()).map_into_right_body());
+                    }
+                },
+                _ => None,
+            };
+
+            let response = match identity {
+                Some(identity) if identity.has_scope(ApiScope::required_for(req.path())) => {
+                    req.extensions_mut().insert(identity);
+                    return service.call(req).await.map(ServiceResponse::map_into_left_body);
+                }
+                Some(_) => HttpResponse::Forbidden().finish(),
+                None => HttpResponse::Unauthorized().finish(),
+            };
+
+            Ok(req.into_response(response).map_into_right_body())
+        })
+    }
+}
@@ -1,26 +1,69 @@
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
-use sea_orm::ActiveValue::Set;
-use serde_with::chrono::Utc;
-
-use entity::prelude::User;
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+use entity::event;
 use entity::user;
-use rusty_interaction::{Builder, defer, slash_command};
+use entity::user::Edition;
+
+use rusty_interaction::{defer, slash_command};
 use rusty_interaction::handler::InteractionHandler;
-use rusty_interaction::types::embed::{EmbedBuilder, EmbedField, EmbedThumbnail};
-use rusty_interaction::types::interaction::{Context, InteractionResponse, WebhookMessage};
+use rusty_interaction::types::interaction::{ApplicationCommandInteractionDataOption, Context, InteractionResponse, WebhookMessage};
+use rusty_interaction::types::Snowflake;
 
-use crate::mojang;
+use crate::{discord, events, links};
+use crate::links::LinkError;
+use crate::mojang::Profiles;
+use crate::discord::{outbox, webhook};
+use crate::rcon::Rcon;
 
 #[defer]
 #[slash_command]
-async fn whitelist_add(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
-    if ctx.interaction.guild_id.is_none() {
+async fn whitelist(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let event = match discord::resolve_event(handler, &ctx).await {
+        Ok(event) => event,
+        Err(response) => return response,
+    };
+
+    if ctx.interaction.member.is_none() {
         return ctx.respond()
-            .content("This command can only be used in a server")
+            .content("This command can only be used by a user")
             .is_ephemeral(true)
             .finish();
     }
 
+    let member = ctx.interaction.member.clone().unwrap();
+
+    let subcommand = ctx.interaction.data.as_ref()
+        .and_then(|data| data.options.as_ref())
+        .and_then(|options| options.first())
+        .cloned();
+
+    match subcommand {
+        Some(subcommand) => match subcommand.name.as_str() {
+            "add" => add(handler, &ctx, &event, &subcommand, member.user.id, &member.roles).await,
+            "remove" => match option(&subcommand, "account") {
+                Some(account) => remove(handler, &ctx, &event, Some(account.as_str()), member.user.id, &member.roles).await,
+                None => invalid_options(&ctx),
+            },
+            "list" => list(handler, &ctx, &event, member.user.id, &member.roles).await,
+            _ => ctx.respond()
+                .content("Unknown subcommand")
+                .is_ephemeral(true)
+                .finish(),
+        },
+        // should never happen but just in case
+        None => something_went_wrong(&ctx),
+    }
+}
+
+/// Removes all of the user's accounts.
+#[defer]
+#[slash_command]
+async fn whitelist_remove(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let event = match discord::resolve_event(handler, &ctx).await {
+        Ok(event) => event,
+        Err(response) => return response,
+    };
+
     if ctx.interaction.member.is_none() {
         return ctx.respond()
             .content("This command can only be used by a user")
@@ -28,120 +71,343 @@ async fn whitelist_add(handler: &mut InteractionHandler, ctx: Context) -> Intera
             .finish();
     }
 
-    let discord_user = ctx.interaction.member.clone().unwrap().user;
+    let member = ctx.interaction.member.clone().unwrap();
+    remove(handler, &ctx, &event, None, member.user.id, &member.roles).await
+}
 
-    if let Some(data) = &ctx.interaction.data {
-        let username_data = data.options.as_ref().unwrap().iter().find(|&option| option.name == "username").unwrap();
-        let username = &username_data.value;
+async fn add(handler: &InteractionHandler, ctx: &Context, event: &event::Model, subcommand: &ApplicationCommandInteractionDataOption, discord_id: Snowflake, roles: &[Snowflake]) -> InteractionResponse {
+    let username = match option(subcommand, "username") {
+        Some(username) => username,
+        None => {
+            return ctx.respond()
+                .content("Please provide your Minecraft username")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+    let edition = match option(subcommand, "bedrock").map(String::as_str) {
+        Some("true") => Edition::Bedrock,
+        _ => Edition::Java,
+    };
 
-        let mojang_result = mojang::resolve_username(username).await;
-        if let Err(e) = &mojang_result {
-            log::error!("Failed to resolve user: {}", e);
+    let profiles = handler.data.get::<Profiles>().expect("Failed to get profile resolver");
+    let response = match profiles.resolve(username, edition).await {
+        Ok(Some(response)) => response,
+        Ok(None) => {
             return ctx.respond()
-                .content("Something went wrong")
+                .content(not_found_message(edition))
                 .is_ephemeral(true)
                 .finish();
         }
-        let response = mojang_result.unwrap();
+        Err(e) => {
+            log::error!("Failed to resolve user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
 
-        match response {
-            None => {
-                return ctx.respond()
-                    .content("That user does not exist!")
-                    .is_ephemeral(true)
-                    .finish();
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+    let limit = events::max_accounts(event, roles);
+
+    let (old, user) = match links::link(db, event.id, discord_id, response.clone(), Some(discord_id), false, limit).await {
+        Ok(result) => result,
+        Err(LinkError::AlreadyLinked(_)) => {
+            let mut content = "This Minecraft account is already linked to another Discord user".to_string();
+            if events::is_moderator(event, roles) {
+                content.push_str(", use `/whitelist-admin add` with `force` to move it");
             }
-            Some(response) => {
-                let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+            return ctx.respond()
+                .content(content)
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(LinkError::LimitReached(0)) => {
+            return ctx.respond()
+                .content("You can't link any Minecraft accounts")
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(LinkError::LimitReached(limit)) => {
+            return ctx.respond()
+                .content(format!("You already linked the maximum of {limit} accounts, remove one with `/whitelist remove` first"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to update user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    if let Some(old) = &old {
+        if old.minecraft_uuid == response.id {
+            return ctx.respond()
+                .content("That user is already whitelisted!")
+                .is_ephemeral(true)
+                .finish();
+        }
+    }
 
-                let db_result = User::find().filter(user::Column::DiscordSnowflake.eq(discord_user.id as u64)).one(db).await;
-                if let Err(e) = &db_result {
-                    log::error!("Failed to get user: {}", e);
+    log::info!("Set new whitelist entry for user {}: {}", discord_id, response.name);
+    push_rcon(handler, event, old.as_ref(), Some(&user), events::is_moderator(event, roles)).await;
+
+    ctx.respond()
+        .content(format!("Successfully added {username} to the whitelist"))
+        .is_ephemeral(true)
+        .finish()
+}
+
+/// Removes the account matching `account`, or all of them.
+async fn remove(handler: &InteractionHandler, ctx: &Context, event: &event::Model, account: Option<&str>, discord_id: Snowflake, roles: &[Snowflake]) -> InteractionResponse {
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+
+    let uuid = match account {
+        Some(account) => match links::find_by_snowflake(db, event.id, discord_id).await {
+            Ok(accounts) => match find_account(&accounts, account) {
+                Some(user) => Some(user.minecraft_uuid),
+                None => {
                     return ctx.respond()
-                        .content("Something went wrong")
+                        .content(format!("You have no linked account {account}, see `/whitelist list`"))
                         .is_ephemeral(true)
                         .finish();
                 }
-                let old: Option<user::Model> = db_result.unwrap();
+            },
+            Err(e) => {
+                log::error!("Failed to get user: {}", e);
+                return something_went_wrong(ctx);
+            }
+        },
+        None => None,
+    };
 
-                if let Some(old) = &old {
-                    if old.minecraft_uuid == response.id {
-                        return ctx.respond()
-                            .content("That user is already whitelisted!")
-                            .is_ephemeral(true)
-                            .finish();
-                    }
-                }
+    let removed = match links::unlink(db, event.id, discord_id, uuid, Some(discord_id)).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            log::error!("Failed to remove user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
 
-                log::info!("Setting new whitelist entry for user {}: {}", discord_user.id, response.name);
-                if let Some(old) = old {
-                    let mut user: user::ActiveModel = old.into();
+    if removed.is_empty() {
+        return ctx.respond()
+            .content("You are not on the whitelist!")
+            .is_ephemeral(true)
+            .finish();
+    }
 
-                    user.minecraft_uuid = Set(response.id);
+    for old in &removed {
+        log::info!("Removed whitelist entry for user {}: {}", discord_id, old.minecraft_uuid);
+        push_rcon(handler, event, Some(old), None, events::is_moderator(event, roles)).await;
+    }
 
-                    user.update(db).await.expect("Failed to update user");
-                } else {
-                    let user = user::ActiveModel {
-                        discord_snowflake: Set(discord_user.id as i64),
-                        minecraft_uuid: Set(response.id),
-                        ..Default::default()
-                    };
+    ctx.respond()
+        .content(format!("Successfully removed {} from the whitelist", display_names(&removed)))
+        .is_ephemeral(true)
+        .finish()
+}
 
-                    user.insert(db).await.expect("Failed to update user");
-                }
+async fn list(handler: &InteractionHandler, ctx: &Context, event: &event::Model, discord_id: Snowflake, roles: &[Snowflake]) -> InteractionResponse {
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
 
-                let webhook = handler.data.get::<crate::discord::webhook::Webhook>();
-                if let Some(webhook) = webhook {
-                    let result = webhook.send(WebhookMessage {
-                        username: Some("WinterJam".to_string()),
-                        avatar_url: Some("https://winterjam.tophatcat.dev/images/util/webhook-logo.png".to_string()),
-                        embeds: Some(vec![EmbedBuilder::default()
-                            .title("Whitelist Update")
-                            .thumbnail(EmbedThumbnail {
-                                url: Some(format!("https://crafthead.net/bust/{}/128", response.id)),
-                                width: Some(128),
-                                height: Some(128),
-                                ..Default::default()
-                            })
-                            .add_field(EmbedField::default()
-                                .name("Discord User")
-                                .value(format!("`{}` <@{}>", discord_user.id, discord_user.id))
-                            )
-                            .add_field(EmbedField::default()
-                                .name("Minecraft Username")
-                                .value(response.name)
-                            )
-                            .add_field(EmbedField::default()
-                                .name("Minecraft UUID")
-                                .value(format!("`{}`", response.id))
-                            )
-                            .timestamp(Utc::now())
-                            .build().unwrap()
-                        ]),
-                        allowed_mentions: Some(Default::default()),
-                        ..Default::default()
-                    }
-                    ).await;
-                    if let Err(e) = result {
-                        log::error!("Failed to send webhook: {}", e)
-                    }
-                }
+    let accounts = match links::find_by_snowflake(db, event.id, discord_id).await {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            log::error!("Failed to get user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    if accounts.is_empty() {
+        return ctx.respond()
+            .content("You are not on the whitelist!")
+            .is_ephemeral(true)
+            .finish();
+    }
+
+    let mut content = format!("Your linked accounts ({} of at most {}):", accounts.len(), events::max_accounts(event, roles));
+    for account in &accounts {
+        let display_name = account.minecraft_name.clone().unwrap_or_else(|| "unknown".to_string());
+        content.push_str(&format!("\n- {display_name} (`{}`, {})", account.minecraft_uuid, edition_name(account.edition)));
+    }
+
+    ctx.respond()
+        .content(content)
+        .is_ephemeral(true)
+        .finish()
+}
+
+#[defer]
+#[slash_command]
+async fn whois(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let event = match discord::resolve_event(handler, &ctx).await {
+        Ok(event) => event,
+        Err(response) => return response,
+    };
+
+    match &ctx.interaction.member {
+        Some(member) if events::is_moderator(&event, &member.roles) => {}
+        _ => {
+            return ctx.respond()
+                .content("Only moderators can use this command")
+                .is_ephemeral(true)
+                .finish();
+        }
+    }
+
+    let options = ctx.interaction.data.as_ref().and_then(|data| data.options.clone()).unwrap_or_default();
+    let user_option = options.iter().find(|&option| option.name == "user").map(|option| option.value.clone());
+    let player_option = options.iter().find(|&option| option.name == "player").map(|option| option.value.clone());
+    let edition = match options.iter().find(|&option| option.name == "bedrock").map(|option| option.value.as_str()) {
+        Some("true") => Edition::Bedrock,
+        _ => Edition::Java,
+    };
 
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+
+    let db_result = match (user_option, player_option) {
+        (Some(user), _) => match user.parse::<Snowflake>() {
+            Ok(snowflake) => links::find_by_snowflake(db, event.id, snowflake).await,
+            Err(_) => {
                 return ctx.respond()
-                    .content(format!("Successfully added {username} to the whitelist"))
+                    .content("Invalid user")
                     .is_ephemeral(true)
                     .finish();
             }
+        },
+        (None, Some(player)) => {
+            let profiles = handler.data.get::<Profiles>().expect("Failed to get profile resolver");
+            let uuid = match Uuid::parse_str(&player) {
+                Ok(uuid) => uuid,
+                Err(_) => match profiles.resolve(&player, edition).await {
+                    Ok(Some(response)) => response.id,
+                    Ok(None) => {
+                        return ctx.respond()
+                            .content(not_found_message(edition))
+                            .is_ephemeral(true)
+                            .finish();
+                    }
+                    Err(e) => {
+                        log::error!("Failed to resolve user: {}", e);
+                        return ctx.respond()
+                            .content("Something went wrong")
+                            .is_ephemeral(true)
+                            .finish();
+                    }
+                },
+            };
+            links::find_by_uuid(db, event.id, uuid).await.map(|user| user.into_iter().collect())
+        }
+        (None, None) => {
+            return ctx.respond()
+                .content("Please provide either a user or a player")
+                .is_ephemeral(true)
+                .finish();
+        }
+    };
+
+    match db_result {
+        Ok(users) if !users.is_empty() => {
+            let mut response = ctx.respond();
+            for user in users {
+                let display_name = user.minecraft_name.unwrap_or_else(|| "unknown".to_string());
+                response.add_embed(webhook::whitelist_embed("Whois", user.discord_snowflake as Snowflake, &display_name, user.minecraft_uuid, None));
+            }
+            response
+                .is_ephemeral(true)
+                .finish()
+        }
+        Ok(_) => {
+            ctx.respond()
+                .content("No linked account found")
+                .is_ephemeral(true)
+                .finish()
+        }
+        Err(e) => {
+            log::error!("Failed to get user: {}", e);
+            ctx.respond()
+                .content("Something went wrong")
+                .is_ephemeral(true)
+                .finish()
         }
     }
+}
+
+/// Queues the console commands that mirror a link change on the RCON servers of the event.
+pub(super) async fn push_rcon(handler: &InteractionHandler, event: &event::Model, old: Option<&user::Model>, new: Option<&user::Model>, operator: bool) {
+    if let Some(rcon) = handler.data.get::<Rcon>() {
+        rcon.push_change(event, old, new, operator).await;
+    }
+}
+
+/// Queues the console command that grants or revokes operator permissions on the RCON servers of
+/// the event.
+pub(super) async fn push_operator(handler: &InteractionHandler, event: &event::Model, user: &user::Model, operator: bool) {
+    if let Some(rcon) = handler.data.get::<Rcon>() {
+        rcon.push_operator(event, user, operator).await;
+    }
+}
+
+/// Finds one of a user's accounts by its UUID or, ignoring case, its name.
+pub(super) fn find_account<'a>(accounts: &'a [user::Model], account: &str) -> Option<&'a user::Model> {
+    let uuid = Uuid::parse_str(account).ok();
+    accounts.iter().find(|user| {
+        Some(user.minecraft_uuid) == uuid
+            || user.minecraft_name.as_ref().is_some_and(|name| name.eq_ignore_ascii_case(account))
+    })
+}
+
+/// Names of the accounts for replies, e.g. `Steve, Alex`.
+pub(super) fn display_names(accounts: &[user::Model]) -> String {
+    accounts.iter()
+        .map(|user| user.minecraft_name.clone().unwrap_or_else(|| user.minecraft_uuid.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn edition_name(edition: Edition) -> &'static str {
+    match edition {
+        Edition::Java => "Java",
+        Edition::Bedrock => "Bedrock",
+    }
+}
+
+pub(super) fn option<'a>(subcommand: &'a ApplicationCommandInteractionDataOption, name: &str) -> Option<&'a String> {
+    subcommand.options.as_ref()?.iter().find(|&option| option.name == name).map(|option| &option.value)
+}
+
+pub(super) fn invalid_options(ctx: &Context) -> InteractionResponse {
+    ctx.respond()
+        .content("Invalid options")
+        .is_ephemeral(true)
+        .finish()
+}
 
-    // should never happen but just in case
+pub(super) fn something_went_wrong(ctx: &Context) -> InteractionResponse {
     ctx.respond()
         .content("Something went wrong")
         .is_ephemeral(true)
         .finish()
 }
 
+/// Reply for a username or gamertag that doesn't belong to any account.
+pub(super) fn not_found_message(edition: Edition) -> &'static str {
+    match edition {
+        Edition::Java => "That user does not exist!",
+        Edition::Bedrock => "That gamertag does not exist!",
+    }
+}
+
+/// Queues a message for the webhook of an event, if it has one.
+///
+/// Only for messages that aren't about a link change, those are queued together with the change.
+pub(super) async fn notify(handler: &InteractionHandler, event: &event::Model, message: WebhookMessage) {
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+    if let Err(e) = outbox::enqueue(db, event.id, &message).await {
+        log::error!("Failed to queue notification: {}", e)
+    }
+}
+
 pub(crate) fn register_commands(handler: &mut InteractionHandler) {
-    handler.add_global_command("whitelist", whitelist_add)
+    handler.add_global_command("whitelist", whitelist);
+    handler.add_global_command("unwhitelist", whitelist_remove);
+    handler.add_global_command("whois", whois);
 }
\ No newline at end of file
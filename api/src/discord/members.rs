@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use actix_web::web::Data;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tokio::sync::{Mutex, RwLock};
+use tokio::sync::mpsc::UnboundedSender;
+use entity::prelude::Event;
+use rusty_interaction::types::Snowflake;
+use crate::discord::rest::DiscordClient;
+
+/// Maximum page size of the list guild members endpoint.
+const PAGE_SIZE: usize = 1000;
+
+/// Caches the roles of guild members so user lookups don't need a Discord request per user.
+///
+/// Each guild's member list is fetched in bulk once its cache is older than the TTL. Users that are
+/// not part of the bulk result (e.g. because they joined since) are fetched individually and the
+/// result, including "not a member", is cached until the next refresh.
+pub(crate) struct MemberCache {
+    client: DiscordClient,
+    ttl: Duration,
+    guilds: RwLock<HashMap<Snowflake, CacheState>>,
+    refresh_lock: Mutex<()>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    changes: Option<UnboundedSender<MemberChange>>,
+}
+
+/// A guild member whose roles changed, or who joined or left the guild, between two refreshes.
+#[derive(Debug)]
+pub(crate) struct MemberChange {
+    pub guild_id: Snowflake,
+    pub snowflake: Snowflake,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Member roles by user, `None` if the user is not a member of the guild.
+    members: HashMap<Snowflake, Option<Vec<Snowflake>>>,
+    refreshed_at: Option<Instant>,
+    /// Whether `members` holds the full member list rather than only individual lookups.
+    complete: bool,
+}
+
+#[derive(Deserialize)]
+struct GuildMember {
+    user: Option<GuildUser>,
+    roles: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GuildUser {
+    id: String,
+}
+
+impl GuildMember {
+    /// Sorted, so role lists can be compared.
+    fn roles(&self) -> Vec<Snowflake> {
+        let mut roles: Vec<Snowflake> = self.roles.iter().filter_map(|role| role.parse().ok()).collect();
+        roles.sort_unstable();
+        roles
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct MemberCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub guilds: usize,
+    pub size: usize,
+    /// Age of the least recently refreshed guild.
+    pub age_seconds: Option<u64>,
+}
+
+impl MemberCache {
+    pub fn new(client: DiscordClient, ttl: Duration) -> Self {
+        Self {
+            client,
+            ttl,
+            guilds: RwLock::new(HashMap::new()),
+            refresh_lock: Mutex::new(()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            changes: None,
+        }
+    }
+
+    /// Reports members whose roles changed whenever a guild is refreshed.
+    pub fn with_change_listener(mut self, changes: UnboundedSender<MemberChange>) -> Self {
+        self.changes = Some(changes);
+        self
+    }
+
+    /// Returns the roles of a guild member, or `None` if the user is not a member of the guild.
+    pub async fn get(&self, guild_id: Snowflake, snowflake: Snowflake) -> anyhow::Result<Option<Vec<Snowflake>>> {
+        self.refresh_if_stale(guild_id).await;
+
+        if let Some(entry) = self.guilds.read().await.get(&guild_id).and_then(|state| state.members.get(&snowflake)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(entry.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let roles = self.fetch_member(guild_id, snowflake).await?;
+        self.guilds.write().await.entry(guild_id).or_default().members.insert(snowflake, roles.clone());
+
+        Ok(roles)
+    }
+
+    pub async fn stats(&self) -> MemberCacheStats {
+        let guilds = self.guilds.read().await;
+        MemberCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            guilds: guilds.len(),
+            size: guilds.values().map(|state| state.members.len()).sum(),
+            age_seconds: guilds.values().filter_map(|state| state.refreshed_at).map(|refreshed_at| refreshed_at.elapsed().as_secs()).max(),
+        }
+    }
+
+    async fn is_stale(&self, guild_id: Snowflake) -> bool {
+        match self.guilds.read().await.get(&guild_id).and_then(|state| state.refreshed_at) {
+            Some(refreshed_at) => refreshed_at.elapsed() >= self.ttl,
+            None => true,
+        }
+    }
+
+    pub async fn refresh_if_stale(&self, guild_id: Snowflake) {
+        if !self.is_stale(guild_id).await {
+            return;
+        }
+
+        let _guard = self.refresh_lock.lock().await;
+        // another request may have refreshed the cache while we were waiting
+        if !self.is_stale(guild_id).await {
+            return;
+        }
+
+        let (members, complete) = match self.fetch_all_members(guild_id).await {
+            Ok(members) => (members, true),
+            Err(e) => {
+                // fall back to individual lookups, e.g. if the bot lacks the GUILD_MEMBERS intent
+                log::warn!("Failed to list guild members, falling back to individual lookups: {}", e);
+                (HashMap::new(), false)
+            }
+        };
+
+        let stats = self.stats().await;
+        log::info!("Refreshed member cache for guild {} with {} members (hits: {}, misses: {})", guild_id, members.len(), stats.hits, stats.misses);
+
+        let mut guilds = self.guilds.write().await;
+        let previous = guilds.insert(guild_id, CacheState {
+            members,
+            refreshed_at: Some(Instant::now()),
+            complete,
+        });
+
+        if let (Some(changes), Some(previous)) = (&self.changes, previous) {
+            // only full member lists can be compared
+            if previous.complete && complete {
+                for (snowflake, change) in changed_members(&previous.members, &guilds[&guild_id].members) {
+                    log::debug!("Member {} of guild {} changed: {}", snowflake, guild_id, change);
+                    let _ = changes.send(MemberChange {
+                        guild_id,
+                        snowflake,
+                    });
+                }
+            }
+        }
+    }
+
+    async fn fetch_all_members(&self, guild_id: Snowflake) -> anyhow::Result<HashMap<Snowflake, Option<Vec<Snowflake>>>> {
+        let mut members = HashMap::new();
+        let mut after: Snowflake = 0;
+
+        loop {
+            let request = self.client.get(&format!("/guilds/{guild_id}/members?limit={PAGE_SIZE}&after={after}")).header(header::ACCEPT, "application/json");
+            let response = self.client.send(request).await
+                .context("Failed to list guild members")?;
+            if !response.status().is_success() {
+                anyhow::bail!("Error listing guild members - {}: {}", response.status(), response.text().await?);
+            }
+
+            let page = response.json::<Vec<GuildMember>>().await.context("unable to parse guild members json response")?;
+            let page_size = page.len();
+
+            for member in page {
+                if let Some(id) = member.user.as_ref().and_then(|user| user.id.parse::<Snowflake>().ok()) {
+                    after = after.max(id);
+                    members.insert(id, Some(member.roles()));
+                }
+            }
+
+            if page_size < PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(members)
+    }
+
+    async fn fetch_member(&self, guild_id: Snowflake, snowflake: Snowflake) -> anyhow::Result<Option<Vec<Snowflake>>> {
+        let request = self.client.get(&format!("/guilds/{guild_id}/members/{snowflake}")).header(header::ACCEPT, "application/json");
+        let response = self.client.send(request).await
+            .context("Failed to get discord user info")?;
+        if !response.status().is_success() {
+            if response.status() != StatusCode::NOT_FOUND {
+                anyhow::bail!("Error getting user from Discord - {}: {}", response.status(), response.text().await?);
+            }
+
+            return Ok(None);
+        }
+
+        let member = response.json::<GuildMember>().await.context("unable to parse guild member json response")?;
+        Ok(Some(member.roles()))
+    }
+}
+
+/// Refreshes the member lists of all event guilds in the background, so role changes are
+/// noticed even if nobody requests the user list.
+pub(crate) async fn keep_fresh(members: Data<MemberCache>, db: DatabaseConnection) {
+    loop {
+        match Event::find().all(&db).await {
+            Ok(events) => {
+                for event in events {
+                    members.refresh_if_stale(event.guild_id as Snowflake).await;
+                }
+            }
+            Err(e) => log::error!("Error getting events from DB: {}", e),
+        }
+
+        tokio::time::sleep(members.ttl).await;
+    }
+}
+
+fn changed_members<'a>(previous: &'a HashMap<Snowflake, Option<Vec<Snowflake>>>, current: &'a HashMap<Snowflake, Option<Vec<Snowflake>>>) -> impl Iterator<Item = (Snowflake, &'static str)> + 'a {
+    let changed = current.iter().filter_map(|(snowflake, roles)| match previous.get(snowflake) {
+        Some(previous_roles) if previous_roles == roles => None,
+        Some(_) => Some((*snowflake, "roles changed")),
+        None => Some((*snowflake, "joined")),
+    });
+    let left = previous.iter()
+        .filter(|(snowflake, roles)| roles.is_some() && !current.contains_key(snowflake))
+        .map(|(snowflake, _)| (*snowflake, "left"));
+
+    changed.chain(left)
+}
@@ -1,51 +1,41 @@
 use sea_orm::DatabaseConnection;
+use entity::event;
 use rusty_interaction::{defer, slash_command};
 use rusty_interaction::handler::InteractionHandler;
 use rusty_interaction::types::interaction::{Context, InteractionResponse};
 use rusty_interaction::types::Snowflake;
-use webhook::Webhook;
+use rest::DiscordClient;
 
 use crate::discord::register::update_global_commands;
+use crate::config::Config;
+use crate::events;
+use crate::mojang::Profiles;
+use crate::rcon::Rcon;
 
 mod register;
 mod commands;
-mod webhook;
+pub(crate) mod members;
+mod moderation;
+pub(crate) mod outbox;
+pub(crate) mod rest;
+pub(crate) mod webhook;
 
-static mut OWNER_ID: Snowflake = 0;
-pub(crate) static mut MODERATOR_ROLES: Vec<Snowflake> = Vec::new();
-pub(crate) static mut GUILD_ID: Snowflake = 0;
-
-pub(crate) async fn init(db: DatabaseConnection) -> anyhow::Result<InteractionHandler> {
+pub(crate) async fn init(db: DatabaseConnection, config: &Config) -> anyhow::Result<InteractionHandler> {
     log::info!("Initializing Discord Module");
 
-    let app_id: Snowflake = std::env::var("DISCORD_APP_ID").expect("DISCORD_APP_ID not set").parse().expect("DISCORD_APP_ID is not a valid Snowflake");
-    let public_key = std::env::var("DISCORD_PUBLIC_KEY").expect("DISCORD_PUBLIC_KEY not set");
-    let token = std::env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN not set");
-    let owner_id: Snowflake = std::env::var("DISCORD_BOT_OWNER_ID").expect("DISCORD_BOT_OWNER_ID not set").parse().expect("DISCORD_OWNER_ID is not a valid Snowflake");
-    let webhook_url = std::env::var("DISCORD_WEBHOOK_URL").ok();
-    let moderator_roles = std::env::var("DISCORD_MODERATOR_ROLES").ok();
-    let guild_id: Option<Snowflake> = std::env::var("DISCORD_GUILD_ID").ok().map(|id| id.parse().expect("DISCORD_GUILD_ID is not a valid Snowflake"));
-
-    unsafe {
-        OWNER_ID = owner_id;
-        GUILD_ID = guild_id.unwrap_or(0);
-    }
-
-    if let Some(roles) = moderator_roles {
-        unsafe {
-            MODERATOR_ROLES = roles.split(",").map(|r| r.parse().expect("DISCORD_MODERATOR_ROLES contains an invalid Snowflake")).collect();
-        }
-    }
+    let app_id = config.discord.app_id;
 
-    let mut handler = InteractionHandler::new(app_id, public_key, Some(&token));
+    let mut handler = InteractionHandler::new(app_id, config.discord.public_key.clone(), Some(&config.discord.token));
+    let client = DiscordClient::new(handler.client().clone(), config.discord.api_base_url.clone());
+    handler.data.insert(Rcon::new(db.clone(), config.discord.guild_id, config.rcon_servers.clone()));
     handler.data.insert(db);
-
-    if let Some(url) = webhook_url {
-        handler.data.insert(Webhook::new(url));
-    }
+    handler.data.insert(config.clone());
+    handler.data.insert(client);
+    handler.data.insert(Profiles::from_config(&config.profiles));
 
     handler.add_global_command("reload", reload_commands);
     commands::register_commands(&mut handler);
+    moderation::register_commands(&mut handler);
     if let Err(e) = update_global_commands(&mut handler, app_id).await {
         log::error!("{}", e);
     }
@@ -53,11 +43,41 @@ pub(crate) async fn init(db: DatabaseConnection) -> anyhow::Result<InteractionHa
     Ok(handler)
 }
 
+/// Looks up the event of the server an interaction was sent from, or the response to send if
+/// there is none.
+pub(crate) async fn resolve_event(handler: &InteractionHandler, ctx: &Context) -> Result<event::Model, InteractionResponse> {
+    let guild_id: Snowflake = match ctx.interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => {
+            return Err(ctx.respond()
+                .content("This command can only be used in a server")
+                .is_ephemeral(true)
+                .finish());
+        }
+    };
+
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+    match events::find_by_guild(db, guild_id).await {
+        Ok(Some(event)) => Ok(event),
+        Ok(None) => Err(ctx.respond()
+            .content("The whitelist is not set up for this server")
+            .is_ephemeral(true)
+            .finish()),
+        Err(e) => {
+            log::error!("Failed to get event: {}", e);
+            Err(ctx.respond()
+                .content("Something went wrong")
+                .is_ephemeral(true)
+                .finish())
+        }
+    }
+}
+
 #[defer]
 #[slash_command]
 async fn reload_commands(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
 
-    let owner = unsafe { OWNER_ID };
+    let owner = handler.data.get::<Config>().expect("Failed to get config").discord.owner_id;
 
     match ctx.author_id {
         Some(id) => {
@@ -0,0 +1,302 @@
+use actix_web::web::Data;
+use sea_orm::DatabaseConnection;
+use entity::event;
+use entity::user::Edition;
+
+use rusty_interaction::{defer, slash_command};
+use rusty_interaction::handler::InteractionHandler;
+use rusty_interaction::types::interaction::{ApplicationCommandInteractionDataOption, Context, InteractionResponse};
+use rusty_interaction::types::Snowflake;
+
+use crate::{discord, events, links};
+use crate::discord::commands::{display_names, find_account, invalid_options, not_found_message, notify, option, push_operator, push_rcon, something_went_wrong};
+use crate::discord::webhook;
+use crate::discord::members::MemberCache;
+use crate::links::LinkError;
+use crate::mojang::Profiles;
+
+#[defer]
+#[slash_command]
+async fn whitelist_admin(handler: &mut InteractionHandler, ctx: Context) -> InteractionResponse {
+    let event = match discord::resolve_event(handler, &ctx).await {
+        Ok(event) => event,
+        Err(response) => return response,
+    };
+
+    if ctx.interaction.member.is_none() {
+        return ctx.respond()
+            .content("This command can only be used by a user")
+            .is_ephemeral(true)
+            .finish();
+    }
+
+    let member = ctx.interaction.member.clone().unwrap();
+    if !events::is_moderator(&event, &member.roles) {
+        return ctx.respond()
+            .content("Only moderators can use this command")
+            .is_ephemeral(true)
+            .finish();
+    }
+    let moderator = member.user.id;
+
+    let subcommand = ctx.interaction.data.as_ref()
+        .and_then(|data| data.options.as_ref())
+        .and_then(|options| options.first())
+        .cloned();
+
+    match subcommand {
+        Some(subcommand) => match subcommand.name.as_str() {
+            "add" => add(handler, &ctx, &event, &subcommand, moderator).await,
+            "remove" => remove(handler, &ctx, &event, &subcommand, moderator).await,
+            "lookup" => lookup(handler, &ctx, &event, &subcommand, moderator).await,
+            "transfer" => transfer(handler, &ctx, &event, &subcommand, moderator).await,
+            _ => ctx.respond()
+                .content("Unknown subcommand")
+                .is_ephemeral(true)
+                .finish(),
+        },
+        // should never happen but just in case
+        None => ctx.respond()
+            .content("Something went wrong")
+            .is_ephemeral(true)
+            .finish(),
+    }
+}
+
+async fn add(handler: &InteractionHandler, ctx: &Context, event: &event::Model, subcommand: &ApplicationCommandInteractionDataOption, moderator: Snowflake) -> InteractionResponse {
+    let (target, username) = match (user_option(subcommand, "user"), option(subcommand, "username")) {
+        (Some(target), Some(username)) => (target, username),
+        _ => return invalid_options(ctx),
+    };
+
+    let profiles = handler.data.get::<Profiles>().expect("Failed to get profile resolver");
+    let edition = match option(subcommand, "bedrock").map(String::as_str) {
+        Some("true") => Edition::Bedrock,
+        _ => Edition::Java,
+    };
+    let response = match profiles.resolve(username, edition).await {
+        Ok(Some(response)) => response,
+        Ok(None) => {
+            return ctx.respond()
+                .content(not_found_message(edition))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to resolve user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    // the target's roles decide how many accounts they may link
+    let roles = match member_roles(handler, event, target).await {
+        Ok(roles) => roles,
+        Err(e) => {
+            log::error!("Failed to get member: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+    let limit = events::max_accounts(event, &roles);
+
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+    let force = option(subcommand, "force").is_some_and(|force| force == "true");
+
+    let (old, user) = match links::link(db, event.id, target, response.clone(), Some(moderator), force, limit).await {
+        Ok(result) => result,
+        Err(LinkError::AlreadyLinked(other)) => {
+            let owner = other.map(|other| format!("<@{other}>")).unwrap_or_else(|| "another user".to_string());
+            return ctx.respond()
+                .content(format!("{} is already linked to {owner}, set `force` to move it to <@{target}>", response.name))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(LinkError::LimitReached(limit)) => {
+            return ctx.respond()
+                .content(format!("<@{target}> already linked the maximum of {limit} accounts, remove one first"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to update user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    if let Some(old) = &old {
+        if old.minecraft_uuid == response.id {
+            return ctx.respond()
+                .content(format!("<@{target}> is already whitelisted as {}", response.name))
+                .is_ephemeral(true)
+                .finish();
+        }
+    }
+
+    log::info!("Moderator {} set whitelist entry for user {}: {}", moderator, target, response.name);
+    push_rcon(handler, event, old.as_ref(), Some(&user), events::is_moderator(event, &roles)).await;
+
+    ctx.respond()
+        .content(format!("Successfully added {} to the whitelist for <@{target}>", response.name))
+        .is_ephemeral(true)
+        .finish()
+}
+
+async fn remove(handler: &InteractionHandler, ctx: &Context, event: &event::Model, subcommand: &ApplicationCommandInteractionDataOption, moderator: Snowflake) -> InteractionResponse {
+    let target = match user_option(subcommand, "user") {
+        Some(target) => target,
+        None => return invalid_options(ctx),
+    };
+
+    let roles = match member_roles(handler, event, target).await {
+        Ok(roles) => roles,
+        Err(e) => {
+            log::error!("Failed to get member: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+
+    // without an account all of the user's accounts are removed
+    let uuid = match option(subcommand, "account") {
+        Some(account) => match links::find_by_snowflake(db, event.id, target).await {
+            Ok(accounts) => match find_account(&accounts, account) {
+                Some(user) => Some(user.minecraft_uuid),
+                None => {
+                    return ctx.respond()
+                        .content(format!("<@{target}> has no linked account {account}"))
+                        .is_ephemeral(true)
+                        .finish();
+                }
+            },
+            Err(e) => {
+                log::error!("Failed to get user: {}", e);
+                return something_went_wrong(ctx);
+            }
+        },
+        None => None,
+    };
+
+    let removed = match links::unlink(db, event.id, target, uuid, Some(moderator)).await {
+        Ok(removed) if !removed.is_empty() => removed,
+        Ok(_) => {
+            return ctx.respond()
+                .content(format!("<@{target}> is not on the whitelist"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to remove user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    for old in &removed {
+        log::info!("Moderator {} removed whitelist entry for user {}: {}", moderator, target, old.minecraft_uuid);
+        push_rcon(handler, event, Some(old), None, events::is_moderator(event, &roles)).await;
+    }
+
+    ctx.respond()
+        .content(format!("Successfully removed {} from the whitelist", display_names(&removed)))
+        .is_ephemeral(true)
+        .finish()
+}
+
+async fn lookup(handler: &InteractionHandler, ctx: &Context, event: &event::Model, subcommand: &ApplicationCommandInteractionDataOption, moderator: Snowflake) -> InteractionResponse {
+    let target = match user_option(subcommand, "user") {
+        Some(target) => target,
+        None => return invalid_options(ctx),
+    };
+
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+
+    let accounts = match links::find_by_snowflake(db, event.id, target).await {
+        Ok(accounts) if !accounts.is_empty() => accounts,
+        Ok(_) => {
+            return ctx.respond()
+                .content(format!("<@{target}> is not on the whitelist"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to get user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    let mut content = format!("<@{target}> is linked to");
+    for user in accounts {
+        let display_name = user.minecraft_name.unwrap_or_else(|| "unknown".to_string());
+        notify(handler, event, webhook::whitelist_message("Whitelist Lookup", target, &display_name, user.minecraft_uuid, Some(moderator))).await;
+        content.push_str(&format!("\n- {display_name} (`{}`)", user.minecraft_uuid));
+    }
+
+    ctx.respond()
+        .content(content)
+        .is_ephemeral(true)
+        .finish()
+}
+
+async fn transfer(handler: &InteractionHandler, ctx: &Context, event: &event::Model, subcommand: &ApplicationCommandInteractionDataOption, moderator: Snowflake) -> InteractionResponse {
+    let (from, to) = match (user_option(subcommand, "from"), user_option(subcommand, "to")) {
+        (Some(from), Some(to)) if from != to => (from, to),
+        _ => return invalid_options(ctx),
+    };
+
+    // the accounts stay whitelisted, only their operator status follows the new owner, whose roles
+    // also decide how many accounts they may have
+    let (from_operator, to_operator, limit) = match (member_roles(handler, event, from).await, member_roles(handler, event, to).await) {
+        (Ok(from_roles), Ok(to_roles)) => (events::is_moderator(event, &from_roles), events::is_moderator(event, &to_roles), events::max_accounts(event, &to_roles)),
+        (Err(e), _) | (_, Err(e)) => {
+            log::error!("Failed to get member: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    let db = handler.data.get::<DatabaseConnection>().expect("Failed to get DB connection");
+
+    let moved = match links::transfer(db, event.id, from, to, Some(moderator), limit).await {
+        Ok(moved) if !moved.is_empty() => moved,
+        Ok(_) => {
+            return ctx.respond()
+                .content(format!("<@{from}> is not on the whitelist"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(LinkError::LimitReached(limit)) => {
+            return ctx.respond()
+                .content(format!("<@{to}> may link at most {limit} accounts, remove some of theirs or <@{from}>'s first"))
+                .is_ephemeral(true)
+                .finish();
+        }
+        Err(e) => {
+            log::error!("Failed to transfer user: {}", e);
+            return something_went_wrong(ctx);
+        }
+    };
+
+    for user in &moved {
+        log::info!("Moderator {} transferred whitelist entry {} from user {} to {}", moderator, user.minecraft_uuid, from, to);
+        if from_operator != to_operator {
+            push_operator(handler, event, user, to_operator).await;
+        }
+    }
+
+    ctx.respond()
+        .content(format!("Successfully transferred {} from <@{from}> to <@{to}>", display_names(&moved)))
+        .is_ephemeral(true)
+        .finish()
+}
+
+/// Roles of a member of the event's guild, none if they left it.
+async fn member_roles(handler: &InteractionHandler, event: &event::Model, target: Snowflake) -> anyhow::Result<Vec<Snowflake>> {
+    let members = handler.data.get::<Data<MemberCache>>().expect("Failed to get member cache");
+    Ok(members.get(event.guild_id as Snowflake, target).await?.unwrap_or_default())
+}
+
+fn user_option(subcommand: &ApplicationCommandInteractionDataOption, name: &str) -> Option<Snowflake> {
+    option(subcommand, name)?.parse().ok()
+}
+
+pub(crate) fn register_commands(handler: &mut InteractionHandler) {
+    handler.add_global_command("whitelist-admin", whitelist_admin);
+}
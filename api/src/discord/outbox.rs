@@ -0,0 +1,95 @@
+use std::time::Duration;
+use chrono::Utc;
+use rusty_interaction::types::interaction::WebhookMessage;
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::ActiveValue::Set;
+use uuid::Uuid;
+use entity::notification_outbox;
+use entity::prelude::{Event, NotificationOutbox};
+use crate::discord::rest::DiscordClient;
+use crate::discord::webhook::Webhook;
+use crate::webhooks;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: u64 = 50;
+/// Notifications are moved to the dead letters after this many attempts.
+pub(crate) const MAX_ATTEMPTS: i32 = 20;
+
+/// Queues a message for the webhook of an event. Does nothing if the event has no webhook.
+///
+/// Pass the transaction of the change the message is about, so the message is sent if and only
+/// if the change is committed.
+pub(crate) async fn enqueue<C: ConnectionTrait>(db: &C, event_id: Uuid, message: &WebhookMessage) -> Result<(), DbErr> {
+    match Event::find_by_id(event_id).one(db).await? {
+        Some(event) if event.webhook_url.is_some() => {}
+        _ => return Ok(()),
+    }
+
+    notification_outbox::ActiveModel {
+        event_id: Set(event_id),
+        payload: Set(serde_json::to_string(message).expect("Failed to serialize webhook message")),
+        ..Default::default()
+    }.insert(db).await?;
+
+    Ok(())
+}
+
+/// Sends queued notifications that are due, forever. Failed notifications are retried with
+/// exponential backoff until they become dead letters after `MAX_ATTEMPTS`.
+pub(crate) async fn deliver_pending(db: DatabaseConnection, client: DiscordClient) {
+    loop {
+        if let Err(e) = deliver_due(&db, &client).await {
+            log::error!("Error delivering notifications: {}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn deliver_due(db: &DatabaseConnection, client: &DiscordClient) -> Result<(), DbErr> {
+    let due = NotificationOutbox::find()
+        .filter(notification_outbox::Column::DeliveredAt.is_null())
+        .filter(notification_outbox::Column::FailedAt.is_null())
+        .filter(notification_outbox::Column::NextAttemptAt.lte(Utc::now()))
+        .order_by_asc(notification_outbox::Column::Id)
+        .limit(BATCH_SIZE)
+        .find_also_related(Event)
+        .all(db).await?;
+
+    for (notification, event) in due {
+        // the webhook is looked up on every attempt, so fixing it also fixes pending notifications
+        let result = match event.and_then(|event| event.webhook_url) {
+            Some(url) => send(client, url, &notification.payload).await,
+            None => Err(anyhow::anyhow!("The event has no webhook")),
+        };
+
+        let attempts = notification.attempts + 1;
+        let id = notification.id;
+        let mut notification: notification_outbox::ActiveModel = notification.into();
+        notification.attempts = Set(attempts);
+        match result {
+            Ok(()) => {
+                notification.delivered_at = Set(Some(Utc::now().into()));
+                notification.last_error = Set(None);
+            }
+            Err(e) => {
+                notification.last_error = Set(Some(e.to_string()));
+                if attempts >= MAX_ATTEMPTS {
+                    log::warn!("Giving up on notification {} after {} attempts: {}", id, attempts, e);
+                    notification.failed_at = Set(Some(Utc::now().into()));
+                } else {
+                    log::debug!("Notification {} failed, retrying: {}", id, e);
+                    notification.next_attempt_at = Set((Utc::now() + chrono::Duration::seconds(webhooks::backoff_seconds(attempts))).into());
+                }
+            }
+        }
+        notification.update(db).await?;
+    }
+
+    Ok(())
+}
+
+async fn send(client: &DiscordClient, url: String, payload: &str) -> anyhow::Result<()> {
+    let message: serde_json::Value = serde_json::from_str(payload)?;
+    Webhook::new(url, client.clone()).send(&message).await
+}
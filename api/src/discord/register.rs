@@ -2,8 +2,7 @@ use rusty_interaction::Builder;
 use rusty_interaction::handler::InteractionHandler;
 use rusty_interaction::types::application::{ApplicationCommand, ApplicationCommandOption, ApplicationCommandOptionType, SlashCommandDefinitionBuilder};
 use rusty_interaction::types::Snowflake;
-
-const BASE_URL: &str = rusty_interaction::BASE_URL;
+use crate::discord::rest::DiscordClient;
 
 pub(crate) async fn update_global_commands(handler: &mut InteractionHandler, app_id: Snowflake) -> anyhow::Result<()> {
     let commands: Vec<ApplicationCommand> = vec![
@@ -14,22 +13,127 @@ pub(crate) async fn update_global_commands(handler: &mut InteractionHandler, app
             .build().unwrap(),
         SlashCommandDefinitionBuilder::default()
             .name("whitelist")
-            .description("Add a user to the whitelist")
+            .description("Manage your whitelisted Minecraft accounts")
+            .add_option(ApplicationCommandOption::default()
+                            .name("add")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Add one of your Minecraft accounts to the whitelist")
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("username")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&true)
+                                            .description("Your Minecraft username, or Xbox gamertag for Bedrock"),
+                            )
+                            .add_option(bedrock_option("Link a Bedrock Edition account, for joining through Geyser")),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("remove")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Remove one of your Minecraft accounts from the whitelist")
+                            .add_option(account_option(true, "The username or UUID of the account")),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("list")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("List your whitelisted Minecraft accounts"),
+            )
+            .build().unwrap(),
+        SlashCommandDefinitionBuilder::default()
+            .name("unwhitelist")
+            .description("Remove all your Minecraft accounts from the whitelist")
+            .build().unwrap(),
+        SlashCommandDefinitionBuilder::default()
+            .name("whois")
+            .description("Find the account linked to a Discord user or Minecraft player")
+            .add_option(ApplicationCommandOption::default()
+                            .name("user")
+                            .option_type(&ApplicationCommandOptionType::User)
+                            .required(&false)
+                            .description("The Discord user to look up"),
+            )
             .add_option(ApplicationCommandOption::default()
-                            .name("username")
+                            .name("player")
                             .option_type(&ApplicationCommandOptionType::String)
-                            .required(&true)
-                            .description("Your Minecraft username"),
+                            .required(&false)
+                            .description("The Minecraft username or UUID to look up"),
+            )
+            .add_option(bedrock_option("Look up the player by Xbox gamertag, for Bedrock Edition accounts"))
+            .build().unwrap(),
+        SlashCommandDefinitionBuilder::default()
+            .name("whitelist-admin")
+            .description("Manage other users' whitelist entries")
+            .add_option(ApplicationCommandOption::default()
+                            .name("add")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Add a user to the whitelist")
+                            .add_option(user_option("user", "The Discord user to link"))
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("username")
+                                            .option_type(&ApplicationCommandOptionType::String)
+                                            .required(&true)
+                                            .description("Their Minecraft username, or Xbox gamertag for Bedrock"),
+                            )
+                            .add_option(bedrock_option("Link a Bedrock Edition account, for joining through Geyser"))
+                            .add_option(ApplicationCommandOption::default()
+                                            .name("force")
+                                            .option_type(&ApplicationCommandOptionType::Boolean)
+                                            .required(&false)
+                                            .description("Move the Minecraft account away from the user it is linked to"),
+                            ),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("remove")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Remove a user from the whitelist")
+                            .add_option(user_option("user", "The Discord user to unlink"))
+                            .add_option(account_option(false, "The username or UUID of the account, all of them if not given")),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("lookup")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Look up a user's whitelist entry")
+                            .add_option(user_option("user", "The Discord user to look up")),
+            )
+            .add_option(ApplicationCommandOption::default()
+                            .name("transfer")
+                            .option_type(&ApplicationCommandOptionType::SubCommand)
+                            .description("Move all whitelist entries of a user to another user")
+                            .add_option(user_option("from", "The Discord user currently linked"))
+                            .add_option(user_option("to", "The Discord user to link instead")),
             )
             .build().unwrap(),
     ];
 
-    let url = format!("{BASE_URL}/applications/{app_id}/commands");
-    let response = handler.client().clone().put(url).json(&commands).send().await?;
+    let client = handler.data.get::<DiscordClient>().expect("Failed to get Discord client");
+    let response = client.send(client.put(&format!("/applications/{app_id}/commands")).json(&commands)).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Failed to update global commands: {:?}", response.text().await?);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn user_option(name: &str, description: &str) -> ApplicationCommandOption {
+    ApplicationCommandOption::default()
+        .name(name)
+        .option_type(&ApplicationCommandOptionType::User)
+        .required(&true)
+        .description(description)
+}
+
+fn bedrock_option(description: &str) -> ApplicationCommandOption {
+    ApplicationCommandOption::default()
+        .name("bedrock")
+        .option_type(&ApplicationCommandOptionType::Boolean)
+        .required(&false)
+        .description(description)
+}
+
+fn account_option(required: bool, description: &str) -> ApplicationCommandOption {
+    ApplicationCommandOption::default()
+        .name("account")
+        .option_type(&ApplicationCommandOptionType::String)
+        .required(&required)
+        .description(description)
+}
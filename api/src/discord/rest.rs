@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// How often a request is retried after being rate limited before giving up.
+const MAX_RETRIES: u32 = 5;
+
+/// Discord REST client that respects rate limits.
+///
+/// Requests are grouped into buckets by route, where IDs are replaced by a placeholder unless
+/// they are a major parameter (channel, guild or webhook). Requests in the same bucket are sent
+/// one after another and wait for the bucket to reset once it is exhausted. A global rate limit
+/// delays all requests. Requests that still hit a 429 are retried after the given `retry_after`.
+#[derive(Clone)]
+pub(crate) struct DiscordClient {
+    client: Client,
+    /// Used for absolute URLs, so the bot token is never sent anywhere else.
+    unauthenticated: Client,
+    base_url: String,
+    limiter: Arc<RateLimiter>,
+}
+
+#[derive(Default)]
+struct RateLimiter {
+    buckets: Mutex<HashMap<String, Arc<Mutex<Bucket>>>>,
+    global_reset: Mutex<Option<Instant>>,
+}
+
+#[derive(Default)]
+struct Bucket {
+    remaining: Option<u32>,
+    reset: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct RateLimitResponse {
+    retry_after: f64,
+    #[serde(default)]
+    global: bool,
+}
+
+impl DiscordClient {
+    /// `client` is expected to send the bot authorization header with every request.
+    pub fn new(client: Client, base_url: String) -> Self {
+        Self {
+            client,
+            unauthenticated: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limiter: Arc::new(RateLimiter::default()),
+        }
+    }
+
+    /// Starts a request to an API path relative to the base URL, e.g. `/guilds/{id}`.
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client.request(method, format!("{}{}", self.base_url, path))
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.request(Method::GET, path)
+    }
+
+    pub fn put(&self, path: &str) -> RequestBuilder {
+        self.request(Method::PUT, path)
+    }
+
+    /// Starts a request to an absolute URL that is rate limited like any other Discord route,
+    /// e.g. an execute webhook URL. It is sent without the bot authorization.
+    pub fn request_url(&self, method: Method, url: &str) -> RequestBuilder {
+        self.unauthenticated.request(method, url)
+    }
+
+    /// Sends a request once its bucket allows it, retrying if it gets rate limited anyway.
+    pub async fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let request = request.build().context("Failed to build Discord request")?;
+        let route = route_key(request.method(), request.url().path());
+        let bucket = self.limiter.bucket(&route).await;
+        // reqwest only applies a client's default headers when it executes the request
+        let client = if request.url().as_str().starts_with(&self.base_url) { &self.client } else { &self.unauthenticated };
+
+        let mut attempt = 0;
+        loop {
+            let mut bucket = bucket.lock().await;
+            self.limiter.wait_global().await;
+            bucket.wait().await;
+
+            let current = match request.try_clone() {
+                Some(current) => current,
+                // bodies that can't be cloned can't be retried either
+                None => return client.execute(request).await.context("Failed to send Discord request"),
+            };
+
+            let response = client.execute(current).await.context("Failed to send Discord request")?;
+            bucket.update(response.headers());
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+
+            attempt += 1;
+            let global = response.headers().get("x-ratelimit-global").is_some();
+            let limit = response.json::<RateLimitResponse>().await.ok();
+            let retry_after = Duration::from_secs_f64(limit.as_ref().map(|limit| limit.retry_after).unwrap_or(1.0).max(0.0));
+
+            if global || limit.map(|limit| limit.global).unwrap_or(false) {
+                self.limiter.set_global(retry_after).await;
+            } else {
+                bucket.remaining = Some(0);
+                bucket.reset = Some(Instant::now() + retry_after);
+            }
+
+            if attempt > MAX_RETRIES {
+                anyhow::bail!("Still rate limited on {} after {} retries", route, MAX_RETRIES);
+            }
+            log::warn!("Rate limited on {}, retrying in {:.1}s", route, retry_after.as_secs_f64());
+        }
+    }
+}
+
+impl RateLimiter {
+    async fn bucket(&self, route: &str) -> Arc<Mutex<Bucket>> {
+        self.buckets.lock().await.entry(route.to_string()).or_default().clone()
+    }
+
+    async fn wait_global(&self) {
+        let reset = *self.global_reset.lock().await;
+        if let Some(reset) = reset {
+            tokio::time::sleep_until(reset).await;
+        }
+    }
+
+    async fn set_global(&self, retry_after: Duration) {
+        log::warn!("Hit the global Discord rate limit, pausing requests for {:.1}s", retry_after.as_secs_f64());
+        *self.global_reset.lock().await = Some(Instant::now() + retry_after);
+    }
+}
+
+impl Bucket {
+    async fn wait(&mut self) {
+        if let (Some(0), Some(reset)) = (self.remaining, self.reset) {
+            tokio::time::sleep_until(reset).await;
+        }
+        if self.reset.is_some_and(|reset| reset <= Instant::now()) {
+            self.remaining = None;
+            self.reset = None;
+        }
+    }
+
+    fn update(&mut self, headers: &HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(remaining) = header("x-ratelimit-remaining").and_then(|value| value.parse().ok()) {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset_after) = header("x-ratelimit-reset-after").and_then(|value| value.parse::<f64>().ok()) {
+            self.reset = Some(Instant::now() + Duration::from_secs_f64(reset_after.max(0.0)));
+        }
+    }
+}
+
+/// Builds the rate limit bucket key for a request, e.g. `GET /guilds/123/members/{id}`.
+fn route_key(method: &Method, path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+
+    let route: Vec<&str> = segments.iter().enumerate().map(|(index, &segment)| {
+        let previous = index.checked_sub(1).map(|index| segments[index]);
+        let is_major = matches!(previous, Some("channels") | Some("guilds") | Some("webhooks"))
+            // the webhook token is part of the major parameter
+            || (index >= 2 && segments[index - 2] == "webhooks");
+
+        if !is_major && !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+            "{id}"
+        } else {
+            segment
+        }
+    }).collect();
+
+    format!("{} {}", method, route.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use super::*;
+
+    /// Status, extra headers and JSON body of a mocked response.
+    type Reply = (u16, &'static [(&'static str, &'static str)], &'static str);
+
+    /// Minimal HTTP server standing in for the Discord API, recording when each path was requested.
+    struct MockServer {
+        base_url: String,
+        requests: Arc<std::sync::Mutex<Vec<(Instant, String)>>>,
+    }
+
+    impl MockServer {
+        /// Answers the requests with `respond`, given the index of the request and its path.
+        async fn start(respond: fn(usize, &str) -> Reply) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}/api/v10", listener.local_addr().unwrap());
+            let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+            let recorded = requests.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let recorded = recorded.clone();
+                    tokio::spawn(async move {
+                        let mut stream = BufReader::new(stream);
+                        loop {
+                            let mut request_line = String::new();
+                            if stream.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+                            let path = request_line.split(' ').nth(1).unwrap_or_default().to_string();
+
+                            let mut content_length = 0;
+                            loop {
+                                let mut header = String::new();
+                                stream.read_line(&mut header).await.unwrap();
+                                if header.trim().is_empty() {
+                                    break;
+                                }
+                                if let Some((name, value)) = header.split_once(':') {
+                                    if name.eq_ignore_ascii_case("content-length") {
+                                        content_length = value.trim().parse().unwrap();
+                                    }
+                                }
+                            }
+                            let mut body = vec![0; content_length];
+                            stream.read_exact(&mut body).await.unwrap();
+
+                            let index = {
+                                let mut recorded = recorded.lock().unwrap();
+                                recorded.push((Instant::now(), path.clone()));
+                                recorded.len() - 1
+                            };
+                            let (status, headers, body) = respond(index, &path);
+
+                            let mut response = format!("HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\n", body.len());
+                            for (name, value) in headers {
+                                response.push_str(&format!("{name}: {value}\r\n"));
+                            }
+                            response.push_str("\r\n");
+                            response.push_str(body);
+                            stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+                        }
+                    });
+                }
+            });
+
+            Self {
+                base_url,
+                requests,
+            }
+        }
+
+        fn client(&self) -> DiscordClient {
+            DiscordClient::new(Client::new(), self.base_url.clone())
+        }
+
+        fn requests(&self) -> Vec<(Instant, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_after_retry_after() {
+        let server = MockServer::start(|index, _| match index {
+            0 => (429, &[], r#"{"retry_after": 0.3, "global": false}"#),
+            _ => (200, &[], "{}"),
+        }).await;
+        let client = server.client();
+
+        let response = client.send(client.get("/channels/1/messages")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].0 - requests[0].0 >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let server = MockServer::start(|_, _| (429, &[], r#"{"retry_after": 0.01}"#)).await;
+        let client = server.client();
+
+        assert!(client.send(client.get("/channels/1/messages")).await.is_err());
+        assert_eq!(server.requests().len(), MAX_RETRIES as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn exhausted_bucket_delays_only_its_route() {
+        let server = MockServer::start(|index, _| match index {
+            0 => (200, &[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset-after", "0.3")], "{}"),
+            _ => (200, &[], "{}"),
+        }).await;
+        let client = server.client();
+
+        client.send(client.request(Method::DELETE, "/channels/1/messages/10")).await.unwrap();
+        client.send(client.request(Method::DELETE, "/channels/2/messages/10")).await.unwrap();
+        client.send(client.request(Method::DELETE, "/channels/1/messages/11")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[1].1, "/api/v10/channels/2/messages/10");
+        assert!(requests[1].0 - requests[0].0 < Duration::from_millis(300));
+        assert_eq!(requests[2].1, "/api/v10/channels/1/messages/11");
+        assert!(requests[2].0 - requests[0].0 >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn global_limit_delays_all_routes() {
+        let server = MockServer::start(|index, _| match index {
+            0 => (429, &[("x-ratelimit-global", "true")], r#"{"retry_after": 0.5, "global": true}"#),
+            _ => (200, &[], "{}"),
+        }).await;
+        let client = server.client();
+
+        let limited = tokio::spawn({
+            let client = client.clone();
+            async move { client.send(client.get("/channels/1/messages")).await.map(|response| response.status()) }
+        });
+        while server.requests().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // give the client time to process the rate limit
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        client.send(client.get("/guilds/2/members")).await.unwrap();
+        assert_eq!(limited.await.unwrap().unwrap(), StatusCode::OK);
+
+        let requests = server.requests();
+        let (other, _) = requests.iter().find(|(_, path)| path == "/api/v10/guilds/2/members").unwrap();
+        assert!(*other - requests[0].0 >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn route_key_groups_by_major_parameters() {
+        assert_eq!(route_key(&Method::GET, "/api/v10/guilds/123/members/456"), "GET /api/v10/guilds/123/members/{id}");
+        assert_eq!(route_key(&Method::PUT, "/api/v10/guilds/123/members/456/roles/789"), "PUT /api/v10/guilds/123/members/{id}/roles/{id}");
+        assert_eq!(route_key(&Method::POST, "/api/v10/channels/1/messages"), "POST /api/v10/channels/1/messages");
+        assert_eq!(route_key(&Method::GET, "/api/v10/users/42"), "GET /api/v10/users/{id}");
+        assert_eq!(route_key(&Method::POST, "/api/webhooks/1/token"), "POST /api/webhooks/1/token");
+    }
+
+    #[test]
+    fn route_key_separates_major_parameters_only() {
+        assert_ne!(route_key(&Method::GET, "/guilds/1/members"), route_key(&Method::GET, "/guilds/2/members"));
+        assert_ne!(route_key(&Method::POST, "/webhooks/1/first"), route_key(&Method::POST, "/webhooks/1/second"));
+        assert_ne!(route_key(&Method::GET, "/channels/1/messages"), route_key(&Method::POST, "/channels/1/messages"));
+        assert_eq!(route_key(&Method::DELETE, "/channels/1/messages/10"), route_key(&Method::DELETE, "/channels/1/messages/11"));
+    }
+}
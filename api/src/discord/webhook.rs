@@ -1,20 +1,30 @@
+use rusty_interaction::Builder;
+use rusty_interaction::types::embed::{Embed, EmbedBuilder, EmbedField, EmbedThumbnail};
 use rusty_interaction::types::interaction::WebhookMessage;
+use rusty_interaction::types::Snowflake;
+use serde_with::chrono::Utc;
+use reqwest::Method;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::discord::rest::DiscordClient;
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub(crate) struct Webhook {
     url: String,
+    client: DiscordClient,
 }
 
 impl Webhook {
-    pub fn new(url: String) -> Self {
+    pub fn new(url: String, client: DiscordClient) -> Self {
         Self {
             url,
+            client,
         }
     }
 
-    pub async fn send(&self, message: WebhookMessage) -> anyhow::Result<()> {
-        let client = reqwest::Client::new();
-        match client.post(&self.url).json(&message).send().await {
+    pub async fn send<T: Serialize + ?Sized>(&self, message: &T) -> anyhow::Result<()> {
+        let request = self.client.request_url(Method::POST, &self.url).json(message);
+        match self.client.send(request).await {
             Ok(response) => {
                 if !response.status().is_success() {
                     anyhow::bail!("Failed to send webhook - {}: {:?}", response.status(), response.text().await?);
@@ -26,4 +36,69 @@ impl Webhook {
         }
         Ok(())
     }
+}
+
+/// Builds the notification embed for a change to a user's whitelist entry.
+///
+/// `moderator` is set when the change was made by someone other than the user themselves.
+pub(crate) fn whitelist_message(title: &str, discord_id: Snowflake, minecraft_name: &str, minecraft_uuid: Uuid, moderator: Option<Snowflake>) -> WebhookMessage {
+    message(whitelist_embed(title, discord_id, minecraft_name, minecraft_uuid, moderator))
+}
+
+/// Builds the notification embed for a linked player who changed their Minecraft username.
+pub(crate) fn name_change_message(discord_id: Snowflake, old_name: Option<&str>, new_name: &str, minecraft_uuid: Uuid) -> WebhookMessage {
+    let embed = embed_builder("Name Changed", discord_id, new_name, minecraft_uuid)
+        .add_field(EmbedField::default()
+            .name("Previous Username")
+            .value(old_name.unwrap_or("unknown").to_string())
+        );
+
+    message(embed.build().unwrap())
+}
+
+fn message(embed: Embed) -> WebhookMessage {
+    WebhookMessage {
+        username: Some("WinterJam".to_string()),
+        avatar_url: Some("https://winterjam.tophatcat.dev/images/util/webhook-logo.png".to_string()),
+        embeds: Some(vec![embed]),
+        allowed_mentions: Some(Default::default()),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn whitelist_embed(title: &str, discord_id: Snowflake, minecraft_name: &str, minecraft_uuid: Uuid, moderator: Option<Snowflake>) -> Embed {
+    let mut embed = embed_builder(title, discord_id, minecraft_name, minecraft_uuid);
+
+    if let Some(moderator) = moderator {
+        embed = embed.add_field(EmbedField::default()
+            .name("Moderator")
+            .value(format!("`{}` <@{}>", moderator, moderator))
+        );
+    }
+
+    embed.build().unwrap()
+}
+
+fn embed_builder(title: &str, discord_id: Snowflake, minecraft_name: &str, minecraft_uuid: Uuid) -> EmbedBuilder {
+    EmbedBuilder::default()
+        .title(title)
+        .thumbnail(EmbedThumbnail {
+            url: Some(format!("https://crafthead.net/bust/{}/128", minecraft_uuid)),
+            width: Some(128),
+            height: Some(128),
+            ..Default::default()
+        })
+        .add_field(EmbedField::default()
+            .name("Discord User")
+            .value(format!("`{}` <@{}>", discord_id, discord_id))
+        )
+        .add_field(EmbedField::default()
+            .name("Minecraft Username")
+            .value(minecraft_name.to_string())
+        )
+        .add_field(EmbedField::default()
+            .name("Minecraft UUID")
+            .value(format!("`{}`", minecraft_uuid))
+        )
+        .timestamp(Utc::now())
 }
\ No newline at end of file
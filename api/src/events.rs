@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::pin::Pin;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::web::Data;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use sea_orm::ActiveValue::Set;
+use uuid::Uuid;
+use entity::event;
+use entity::prelude::Event;
+use rusty_interaction::types::Snowflake;
+use crate::auth::ApiKeyIdentity;
+use crate::config::Config;
+use crate::status;
+
+/// Guild ID of the event the migration moved pre-existing links to.
+const PLACEHOLDER_GUILD_ID: i64 = 0;
+
+pub(crate) async fn find_by_guild(db: &DatabaseConnection, guild_id: Snowflake) -> Result<Option<event::Model>, DbErr> {
+    Event::find().filter(event::Column::GuildId.eq(guild_id as i64)).one(db).await
+}
+
+/// Moderator roles are stored comma separated.
+pub(crate) fn moderator_roles(event: &event::Model) -> Vec<Snowflake> {
+    event.moderator_roles.split(',').filter_map(|role| role.trim().parse().ok()).collect()
+}
+
+pub(crate) fn format_moderator_roles(roles: &[Snowflake]) -> String {
+    roles.iter().map(Snowflake::to_string).collect::<Vec<_>>().join(",")
+}
+
+pub(crate) fn is_moderator(event: &event::Model, roles: &[Snowflake]) -> bool {
+    let moderator_roles = moderator_roles(event);
+    roles.iter().any(|r| moderator_roles.contains(r))
+}
+
+/// Per-role overrides of the account limit are stored comma separated as `role:limit`.
+pub(crate) fn account_limits(event: &event::Model) -> Vec<(Snowflake, u32)> {
+    event.account_limits.split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .filter_map(|(role, limit)| Some((role.trim().parse().ok()?, limit.trim().parse().ok()?)))
+        .collect()
+}
+
+pub(crate) fn format_account_limits(limits: &[(Snowflake, u32)]) -> String {
+    limits.iter().map(|(role, limit)| format!("{role}:{limit}")).collect::<Vec<_>>().join(",")
+}
+
+/// How many Minecraft accounts a member with these roles may link. Role overrides replace the
+/// event's default, the highest one of the member's roles applies.
+pub(crate) fn max_accounts(event: &event::Model, roles: &[Snowflake]) -> u32 {
+    account_limits(event).into_iter()
+        .filter(|(role, _)| roles.contains(role))
+        .map(|(_, limit)| limit)
+        .max()
+        .unwrap_or(event.max_accounts.max(0) as u32)
+}
+
+/// Makes sure the guild from the configuration has an event, so a single-guild setup works
+/// without creating one through the API.
+///
+/// Links that existed before events were introduced belong to a placeholder event, which is
+/// claimed for the configured guild.
+pub(crate) async fn bootstrap(db: &DatabaseConnection, config: &Config) -> Result<(), DbErr> {
+    let guild_id = match config.discord.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    if find_by_guild(db, guild_id).await?.is_some() {
+        return Ok(());
+    }
+
+    let moderator_roles = format_moderator_roles(&config.discord.moderator_roles);
+    match Event::find().filter(event::Column::GuildId.eq(PLACEHOLDER_GUILD_ID)).one(db).await? {
+        Some(placeholder) => {
+            log::info!("Assigning existing links to guild {}", guild_id);
+            let mut event: event::ActiveModel = placeholder.into();
+            event.guild_id = Set(guild_id as i64);
+            event.moderator_roles = Set(moderator_roles);
+            event.webhook_url = Set(config.discord.webhook_url.clone());
+            event.update(db).await?;
+        }
+        None => {
+            log::info!("Creating event for guild {}", guild_id);
+            event::ActiveModel {
+                name: Set("default".to_string()),
+                guild_id: Set(guild_id as i64),
+                moderator_roles: Set(moderator_roles),
+                webhook_url: Set(config.discord.webhook_url.clone()),
+                ..Default::default()
+            }.insert(db).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The event an API request applies to.
+///
+/// Keys bound to an event always use that event. Other keys can pick one with the `x-event-id`
+/// header and otherwise fall back to the event of the configured guild.
+pub(crate) struct CurrentEvent(pub event::Model);
+
+impl FromRequest for CurrentEvent {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let identity = req.extensions().get::<ApiKeyIdentity>().cloned();
+        let requested = req.headers().get("x-event-id").map(|value| value.to_str().ok().and_then(|value| Uuid::parse_str(value).ok()));
+        let db = req.app_data::<Data<DatabaseConnection>>().cloned();
+        let config = req.app_data::<Data<Config>>().cloned();
+
+        Box::pin(async move {
+            let db = db.expect("Failed to get DB connection");
+            let config = config.expect("Failed to get config");
+
+            let requested = match requested {
+                Some(Some(id)) => Some(id),
+                Some(None) => return Err(error(status::err_bad_request("x-event-id must be a UUID"))),
+                None => None,
+            };
+
+            let bound = identity.and_then(|identity| identity.event_id);
+            let result = match (bound, requested) {
+                (Some(bound), Some(requested)) if bound != requested => {
+                    return Err(error(status::err_forbidden("This API key cannot access that event")));
+                }
+                (Some(id), _) | (None, Some(id)) => Event::find_by_id(id).one(db.get_ref()).await,
+                (None, None) => match config.discord.guild_id {
+                    Some(guild_id) => find_by_guild(db.get_ref(), guild_id).await,
+                    None => return Err(error(status::err_bad_request("x-event-id is required"))),
+                },
+            };
+
+            match result {
+                Ok(Some(event)) => Ok(CurrentEvent(event)),
+                Ok(None) => Err(error(status::err_not_found())),
+                Err(e) => {
+                    log::error!("Error getting event from DB: {}", e);
+                    Err(error(status::err_server("Error getting event from DB")))
+                }
+            }
+        })
+    }
+}
+
+fn error(response: actix_web::HttpResponse) -> actix_web::Error {
+    InternalError::from_response("", response).into()
+}
@@ -0,0 +1,60 @@
+use sea_orm::{ActiveModelTrait, ConnectionTrait, DatabaseConnection, DbErr};
+use sea_orm::ActiveValue::Set;
+use tokio::sync::mpsc::UnboundedReceiver;
+use uuid::Uuid;
+use entity::link_history;
+use entity::link_history::LinkAction;
+use rusty_interaction::types::Snowflake;
+use crate::{events, links, webhooks};
+use crate::discord::members::MemberChange;
+
+/// Records a change to a user's whitelist link in the audit log.
+///
+/// `actor` is the Discord user who made the change, or `None` if it was made through the API.
+/// The change is also queued for the event's webhook subscriptions.
+pub(crate) async fn record<C: ConnectionTrait>(db: &C, event_id: Uuid, action: LinkAction, snowflake: Snowflake, actor: Option<Snowflake>, old_uuid: Option<Uuid>, new_uuid: Option<Uuid>) -> Result<(), DbErr> {
+    let entry = link_history::ActiveModel {
+        event_id: Set(Some(event_id)),
+        discord_snowflake: Set(snowflake as i64),
+        actor_snowflake: Set(actor.map(|actor| actor as i64)),
+        action: Set(action),
+        old_uuid: Set(old_uuid),
+        new_uuid: Set(new_uuid),
+        ..Default::default()
+    }.insert(db).await?;
+
+    webhooks::enqueue(db, &entry).await
+}
+
+/// Name of a change as sent to stream and webhook consumers.
+pub(crate) fn change_kind(action: LinkAction) -> &'static str {
+    match action {
+        LinkAction::Insert => "linked",
+        LinkAction::Update => "relinked",
+        LinkAction::Remove => "unlinked",
+        LinkAction::RoleChange => "role_changed",
+    }
+}
+
+/// Records role changes of linked users reported by the member cache, so they show up in the
+/// history and the change stream.
+pub(crate) async fn record_role_changes(db: DatabaseConnection, mut changes: UnboundedReceiver<MemberChange>) {
+    while let Some(change) = changes.recv().await {
+        if let Err(e) = record_role_change(&db, &change).await {
+            log::error!("Failed to record role change of {} in guild {}: {}", change.snowflake, change.guild_id, e);
+        }
+    }
+}
+
+async fn record_role_change(db: &DatabaseConnection, change: &MemberChange) -> Result<(), DbErr> {
+    let event = match events::find_by_guild(db, change.guild_id).await? {
+        Some(event) => event,
+        None => return Ok(()),
+    };
+
+    for user in links::find_by_snowflake(db, event.id, change.snowflake).await? {
+        record(db, event.id, LinkAction::RoleChange, change.snowflake, None, Some(user.minecraft_uuid), Some(user.minecraft_uuid)).await?;
+    }
+
+    Ok(())
+}
@@ -4,20 +4,29 @@ mod status;
 mod discord;
 mod admin;
 mod mojang;
+mod names;
 mod api;
+mod rcon;
+mod history;
+mod links;
+mod config;
+mod auth;
+mod events;
+mod webhooks;
 
-use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 use std::time::Duration;
 use sea_orm::{ConnectOptions, Database};
 use migration::{Migrator, MigratorTrait};
+use crate::config::{Config, ListenAddress};
 
 pub async fn start() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = Config::load()?;
 
-    let mut opts = ConnectOptions::new(db_url);
+    let mut opts = ConnectOptions::new(config.database_url.clone());
     opts.max_connections(3)
         .min_connections(1)
         .connect_timeout(Duration::from_secs(10))
@@ -29,8 +38,31 @@ pub async fn start() -> anyhow::Result<()> {
 
     let db = Database::connect(opts).await?;
     Migrator::up(&db, None).await?;
+    events::bootstrap(&db, &config).await?;
 
-    server::server_main(db.clone()).await?;
+    server::server_main(db.clone(), config).await?;
 
     Ok(())
 }
+
+/// URL of the health endpoint on the first configured TCP listen address.
+pub fn healthcheck_url() -> anyhow::Result<String> {
+    dotenvy::dotenv().ok();
+
+    let address = config::load_listen_addresses()?.into_iter()
+        .find_map(|address| match address {
+            ListenAddress::Tcp(address) => Some(address),
+            ListenAddress::Unix(_) => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("No TCP listen address configured"))?;
+
+    let address = address.to_socket_addrs()?.next()
+        .ok_or_else(|| anyhow::anyhow!("Failed to resolve {address}"))?;
+    let address = match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => SocketAddr::new(Ipv4Addr::LOCALHOST.into(), address.port()),
+        IpAddr::V6(ip) if ip.is_unspecified() => SocketAddr::new(Ipv6Addr::LOCALHOST.into(), address.port()),
+        _ => address,
+    };
+
+    Ok(format!("http://{address}/_health"))
+}
\ No newline at end of file
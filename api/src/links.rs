@@ -0,0 +1,225 @@
+use std::fmt::{Display, Formatter};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, SqlErr, Statement, TransactionError, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use uuid::Uuid;
+use entity::link_history::LinkAction;
+use entity::prelude::User;
+use entity::user;
+use rusty_interaction::types::Snowflake;
+use crate::history;
+use crate::mojang::{bedrock, MojangResponse};
+use crate::discord::{outbox, webhook};
+
+/// All accounts a Discord user linked within an event, oldest first.
+pub(crate) async fn find_by_snowflake<C: ConnectionTrait>(db: &C, event_id: Uuid, snowflake: Snowflake) -> Result<Vec<user::Model>, DbErr> {
+    User::find()
+        .filter(user::Column::EventId.eq(event_id))
+        .filter(user::Column::DiscordSnowflake.eq(snowflake as i64))
+        .order_by_asc(user::Column::CreatedAt)
+        .all(db).await
+}
+
+/// Locks the links of a Discord user within an event until the transaction ends, so concurrent
+/// changes can't both pass the account limit. Rows can't be locked for this, as a user without
+/// links has none.
+pub(crate) async fn lock_links<C: ConnectionTrait>(txn: &C, event_id: Uuid, snowflake: Snowflake) -> Result<(), DbErr> {
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+        [format!("{event_id}:{snowflake}").into()],
+    )).await?;
+    Ok(())
+}
+
+pub(crate) async fn find_by_uuid(db: &DatabaseConnection, event_id: Uuid, uuid: Uuid) -> Result<Option<user::Model>, DbErr> {
+    User::find().filter(user::Column::EventId.eq(event_id)).filter(user::Column::MinecraftUuid.eq(uuid)).one(db).await
+}
+
+#[derive(Debug)]
+pub(crate) enum LinkError {
+    /// The Minecraft account is linked to another Discord user of the event. `None` if a
+    /// concurrent link of the same account was only caught by the unique index.
+    AlreadyLinked(Option<Snowflake>),
+    /// The Discord user already linked as many accounts as they may.
+    LimitReached(u32),
+    Db(DbErr),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::AlreadyLinked(Some(other)) => write!(f, "The Minecraft account is already linked to {other}"),
+            LinkError::AlreadyLinked(None) => write!(f, "The Minecraft account is already linked to another user"),
+            LinkError::LimitReached(limit) => write!(f, "The Discord user already linked the maximum of {limit} accounts"),
+            LinkError::Db(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<DbErr> for LinkError {
+    fn from(e: DbErr) -> Self {
+        match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(message)) if message.contains("minecraft_uuid") => LinkError::AlreadyLinked(None),
+            _ => LinkError::Db(e),
+        }
+    }
+}
+
+/// Links a Minecraft account to a Discord user within an event.
+///
+/// Users may link up to `limit` accounts. At a limit of one their account is replaced, as before
+/// multiple accounts were possible, at other limits linking fails with [`LinkError::LimitReached`].
+///
+/// Returns the link that was replaced or already existed alongside the new one. If the user was
+/// already linked to the account only the stored name is refreshed and no history is recorded or
+/// notification sent.
+///
+/// Fails with [`LinkError::AlreadyLinked`] if another user is linked to the account, unless
+/// `force` is set, in which case that user's link is removed first.
+pub(crate) async fn link(db: &DatabaseConnection, event_id: Uuid, snowflake: Snowflake, profile: MojangResponse, actor: Option<Snowflake>, force: bool, limit: u32) -> Result<(Option<user::Model>, user::Model), LinkError> {
+    let MojangResponse { id: uuid, name } = profile;
+
+    db.transaction::<_, (Option<user::Model>, user::Model), LinkError>(|txn| {
+        Box::pin(async move {
+            lock_links(txn, event_id, snowflake).await?;
+
+            let other = User::find()
+                .filter(user::Column::EventId.eq(event_id))
+                .filter(user::Column::MinecraftUuid.eq(uuid))
+                .filter(user::Column::DiscordSnowflake.ne(snowflake as i64))
+                .one(txn).await?;
+            if let Some(other) = other {
+                let other_snowflake = other.discord_snowflake as Snowflake;
+                if !force {
+                    return Err(LinkError::AlreadyLinked(Some(other_snowflake)));
+                }
+
+                other.clone().delete(txn).await?;
+                history::record(txn, event_id, LinkAction::Remove, other_snowflake, actor, Some(uuid), None).await?;
+                notify(txn, event_id, "Whitelist Removal", other_snowflake, &other, actor).await?;
+            }
+
+            let accounts = find_by_snowflake(txn, event_id, snowflake).await?;
+
+            if let Some(old) = accounts.iter().find(|account| account.minecraft_uuid == uuid) {
+                if old.minecraft_name.as_ref() == Some(&name) {
+                    return Ok((Some(old.clone()), old.clone()));
+                }
+
+                let mut user: user::ActiveModel = old.clone().into();
+                user.minecraft_name = Set(Some(name));
+                user.updated_at = Set(Utc::now().into());
+                return Ok((Some(old.clone()), user.update(txn).await?));
+            }
+
+            let (old, user) = match (limit, accounts.as_slice()) {
+                (1, [old]) => {
+                    let mut user: user::ActiveModel = old.clone().into();
+                    user.minecraft_uuid = Set(uuid);
+                    user.minecraft_name = Set(Some(name));
+                    user.edition = Set(bedrock::edition(uuid));
+                    user.updated_at = Set(Utc::now().into());
+                    let user = user.update(txn).await?;
+                    history::record(txn, event_id, LinkAction::Update, snowflake, actor, Some(old.minecraft_uuid), Some(uuid)).await?;
+                    notify(txn, event_id, "Whitelist Update", snowflake, &user, actor).await?;
+                    (Some(old.clone()), user)
+                }
+                (limit, accounts) if accounts.len() as u32 >= limit => return Err(LinkError::LimitReached(limit)),
+                _ => {
+                    let user = user::ActiveModel {
+                        event_id: Set(event_id),
+                        discord_snowflake: Set(snowflake as i64),
+                        minecraft_uuid: Set(uuid),
+                        minecraft_name: Set(Some(name)),
+                        edition: Set(bedrock::edition(uuid)),
+                        ..Default::default()
+                    }.insert(txn).await?;
+                    history::record(txn, event_id, LinkAction::Insert, snowflake, actor, None, Some(uuid)).await?;
+                    notify(txn, event_id, "Whitelist Update", snowflake, &user, actor).await?;
+                    (None, user)
+                }
+            };
+
+            Ok((old, user))
+        })
+    }).await.map_err(|e| match e {
+        TransactionError::Connection(e) => e.into(),
+        TransactionError::Transaction(e) => e,
+    })
+}
+
+/// Removes one of a Discord user's accounts, or all of them without `account`, returning the
+/// removed links.
+pub(crate) async fn unlink(db: &DatabaseConnection, event_id: Uuid, snowflake: Snowflake, account: Option<Uuid>, actor: Option<Snowflake>) -> Result<Vec<user::Model>, DbErr> {
+    db.transaction::<_, Vec<user::Model>, DbErr>(|txn| {
+        Box::pin(async move {
+            let accounts: Vec<user::Model> = find_by_snowflake(txn, event_id, snowflake).await?.into_iter()
+                .filter(|old| account.map_or(true, |account| old.minecraft_uuid == account))
+                .collect();
+
+            for old in &accounts {
+                old.clone().delete(txn).await?;
+                history::record(txn, event_id, LinkAction::Remove, snowflake, actor, Some(old.minecraft_uuid), None).await?;
+                notify(txn, event_id, "Whitelist Removal", snowflake, old, actor).await?;
+            }
+
+            Ok(accounts)
+        })
+    }).await.map_err(transaction_error)
+}
+
+/// Moves all accounts of one Discord user to another, who may link up to `limit` accounts in
+/// total. Fails with [`LinkError::LimitReached`] if the accounts don't fit.
+pub(crate) async fn transfer(db: &DatabaseConnection, event_id: Uuid, from: Snowflake, to: Snowflake, actor: Option<Snowflake>, limit: u32) -> Result<Vec<user::Model>, LinkError> {
+    db.transaction::<_, Vec<user::Model>, LinkError>(|txn| {
+        Box::pin(async move {
+            // always in the same order, so opposite transfers can't deadlock
+            lock_links(txn, event_id, from.min(to)).await?;
+            lock_links(txn, event_id, from.max(to)).await?;
+
+            let accounts = find_by_snowflake(txn, event_id, from).await?;
+            let existing = find_by_snowflake(txn, event_id, to).await?;
+            if !accounts.is_empty() && (existing.len() + accounts.len()) as u32 > limit {
+                return Err(LinkError::LimitReached(limit));
+            }
+
+            let mut moved = Vec::new();
+            for old in accounts {
+                let uuid = old.minecraft_uuid;
+
+                let mut user: user::ActiveModel = old.into();
+                user.discord_snowflake = Set(to as i64);
+                user.updated_at = Set(Utc::now().into());
+                let user = user.update(txn).await?;
+
+                history::record(txn, event_id, LinkAction::Remove, from, actor, Some(uuid), None).await?;
+                history::record(txn, event_id, LinkAction::Insert, to, actor, None, Some(uuid)).await?;
+                notify(txn, event_id, &format!("Whitelist Transfer from {from}"), to, &user, actor).await?;
+                moved.push(user);
+            }
+
+            Ok(moved)
+        })
+    }).await.map_err(|e| match e {
+        TransactionError::Connection(e) => e.into(),
+        TransactionError::Transaction(e) => e,
+    })
+}
+
+/// Queues the webhook notification about a change to a user's link. The change is shown as made by
+/// a moderator if the actor is someone other than the user.
+async fn notify<C: ConnectionTrait>(db: &C, event_id: Uuid, title: &str, snowflake: Snowflake, user: &user::Model, actor: Option<Snowflake>) -> Result<(), DbErr> {
+    let display_name = user.minecraft_name.clone().unwrap_or_else(|| user.minecraft_uuid.to_string());
+    let moderator = actor.filter(|&actor| actor != snowflake);
+    outbox::enqueue(db, event_id, &webhook::whitelist_message(title, snowflake, &display_name, user.minecraft_uuid, moderator)).await
+}
+
+fn transaction_error(e: TransactionError<DbErr>) -> DbErr {
+    match e {
+        TransactionError::Connection(e) => e,
+        TransactionError::Transaction(e) => e,
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use entity::user::Edition;
+use crate::mojang::providers;
+
+const GEYSER_URL: &str = "https://api.geysermc.org/v2/xbox/xuid";
+/// Floodgate cuts usernames off at the Java limit, including its prefix.
+const MAX_USERNAME_LENGTH: usize = 16;
+
+/// A source of Xbox XUIDs, which Floodgate derives the UUIDs of Bedrock players from.
+#[async_trait]
+pub(crate) trait XuidResolver: Send + Sync {
+    /// Name of the provider for logging.
+    fn name(&self) -> &str;
+
+    /// Looks up the XUID of a gamertag, `None` if there is no Xbox account with that gamertag.
+    async fn resolve_gamertag(&self, gamertag: &str) -> anyhow::Result<Option<u64>>;
+}
+
+/// The GeyserMC global API.
+pub(super) struct Geyser {
+    client: Client,
+}
+
+impl Geyser {
+    pub fn new() -> Self {
+        Self {
+            client: providers::client(),
+        }
+    }
+}
+
+#[async_trait]
+impl XuidResolver for Geyser {
+    fn name(&self) -> &str {
+        "GeyserMC"
+    }
+
+    async fn resolve_gamertag(&self, gamertag: &str) -> anyhow::Result<Option<u64>> {
+        // gamertags are validated, spaces and `#` are the only characters that need escaping
+        let gamertag = gamertag.replace(' ', "%20").replace('#', "%23");
+        let response = self.client.get(format!("{GEYSER_URL}/{gamertag}")).send().await
+            .context("Failed to resolve gamertag")?;
+
+        // unknown gamertags are reported as client errors
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            anyhow::bail!("Failed to resolve gamertag - {}: {:?}", status, response.text().await);
+        }
+        if status.is_client_error() {
+            return Ok(None);
+        }
+
+        let value = response.json::<GeyserResponse>().await
+            .context("Failed to parse response")?;
+
+        Ok(value.xuid)
+    }
+}
+
+#[derive(Deserialize)]
+struct GeyserResponse {
+    xuid: Option<u64>,
+}
+
+/// Makes up an XUID for every gamertag, derived from the gamertag, for development without
+/// network access.
+pub(super) struct Stub;
+
+#[async_trait]
+impl XuidResolver for Stub {
+    fn name(&self) -> &str {
+        "stub"
+    }
+
+    async fn resolve_gamertag(&self, gamertag: &str) -> anyhow::Result<Option<u64>> {
+        let hash = Sha256::digest(gamertag.to_lowercase().as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash[..8]);
+
+        Ok(Some(u64::from_be_bytes(bytes)))
+    }
+}
+
+/// The UUID Floodgate gives the Bedrock player with this XUID.
+pub(crate) fn floodgate_uuid(xuid: u64) -> Uuid {
+    Uuid::from_u64_pair(0, xuid)
+}
+
+/// Tells the edition of an account by its UUID. Floodgate UUIDs only use the lower half, which
+/// is never the case for UUIDs handed out by Mojang.
+pub(crate) fn edition(uuid: Uuid) -> Edition {
+    match uuid.as_u64_pair() {
+        (0, _) => Edition::Bedrock,
+        _ => Edition::Java,
+    }
+}
+
+/// The username Floodgate shows a Bedrock player as on the Java server.
+pub(super) fn floodgate_name(prefix: &str, gamertag: &str) -> String {
+    format!("{prefix}{}", gamertag.replace(' ', "_"))
+        .chars()
+        .take(MAX_USERNAME_LENGTH)
+        .collect()
+}
+
+/// Gamertags are letters, digits and spaces, modern ones may end in a `#` and a numeric suffix.
+pub(super) fn is_valid_gamertag(gamertag: &str) -> bool {
+    !gamertag.is_empty()
+        && gamertag.len() <= 20
+        && !gamertag.starts_with(' ')
+        && gamertag.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '#')
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use entity::user::Edition;
+use crate::config::{BedrockProvider, ProfileConfig, ProfileProvider};
+use bedrock::{Geyser, XuidResolver};
+use providers::{Mojang, MOJANG_URL, PlayerDb, SESSION_SERVER_URL, Stub};
+
+pub(crate) mod bedrock;
+mod providers;
+
+/// Usernames without a profile are only cached this long, as someone may take the name any time.
+const NOT_FOUND_TTL: Duration = Duration::from_secs(60);
+/// Lookups of arbitrary names through /whois would grow the cache without bound otherwise.
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// A source of Minecraft profiles.
+#[async_trait]
+pub(crate) trait ProfileResolver: Send + Sync {
+    /// Name of the provider for logging.
+    fn name(&self) -> &str;
+
+    /// Looks up the profile of a username, `None` if there is no player with that name.
+    async fn resolve_username(&self, username: &str) -> anyhow::Result<Option<MojangResponse>>;
+
+    /// Looks up the current profile of a player, `None` if the provider doesn't know the player.
+    async fn resolve_uuid(&self, uuid: Uuid) -> anyhow::Result<Option<MojangResponse>>;
+}
+
+/// Resolves profiles through the configured providers and caches the results.
+///
+/// When a provider fails (e.g. Mojang is down or rate limiting us) the next one is tried, and if
+/// all of them fail an expired cache entry is used rather than failing the lookup.
+///
+/// Bedrock players are resolved to the profile Floodgate gives them instead.
+#[derive(Clone)]
+pub(crate) struct Profiles {
+    providers: Arc<Vec<Box<dyn ProfileResolver>>>,
+    bedrock: Arc<dyn XuidResolver>,
+    floodgate_prefix: String,
+    ttl: Duration,
+    cache: Arc<RwLock<HashMap<String, CacheEntry>>>,
+}
+
+struct CacheEntry {
+    profile: Option<MojangResponse>,
+    fetched_at: Instant,
+}
+
+impl Profiles {
+    pub fn new(providers: Vec<Box<dyn ProfileResolver>>, bedrock: Arc<dyn XuidResolver>, floodgate_prefix: String, ttl: Duration) -> Self {
+        Self {
+            providers: Arc::new(providers),
+            bedrock,
+            floodgate_prefix,
+            ttl,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn from_config(config: &ProfileConfig) -> Self {
+        let providers = config.providers.iter()
+            .map(|provider| -> Box<dyn ProfileResolver> {
+                match provider {
+                    ProfileProvider::Mojang => Box::new(Mojang::new(MOJANG_URL.to_string(), SESSION_SERVER_URL.to_string())),
+                    ProfileProvider::Mirror => {
+                        let url = config.mirror_url.clone().expect("Mirror URL is not configured");
+                        Box::new(Mojang::new(url.clone(), url))
+                    }
+                    ProfileProvider::PlayerDb => Box::new(PlayerDb::new()),
+                    ProfileProvider::Stub => Box::new(Stub),
+                }
+            })
+            .collect();
+
+        let bedrock: Arc<dyn XuidResolver> = match config.bedrock_provider {
+            BedrockProvider::Geyser => Arc::new(Geyser::new()),
+            BedrockProvider::Stub => Arc::new(bedrock::Stub),
+        };
+
+        Self::new(providers, bedrock, config.floodgate_prefix.clone(), config.cache_ttl)
+    }
+
+    /// Looks up the profile of a Java username or Bedrock gamertag.
+    pub async fn resolve(&self, name: &str, edition: Edition) -> anyhow::Result<Option<MojangResponse>> {
+        match edition {
+            Edition::Java => self.resolve_username(name).await,
+            Edition::Bedrock => self.resolve_gamertag(name).await,
+        }
+    }
+
+    /// Looks up the profile of a username, `None` if there is no player with that name.
+    pub async fn resolve_username(&self, username: &str) -> anyhow::Result<Option<MojangResponse>> {
+        if !is_valid_username(username) {
+            return Ok(None);
+        }
+
+        let key = username.to_lowercase();
+        if let Some(entry) = self.cache.read().await.get(&key) {
+            if !self.is_expired(entry) {
+                return Ok(entry.profile.clone());
+            }
+        }
+
+        let mut error = None;
+        for provider in self.providers.iter() {
+            match provider.resolve_username(username).await {
+                Ok(profile) => {
+                    self.cache_profile(key, profile.clone()).await;
+                    return Ok(profile);
+                }
+                Err(e) => {
+                    log::warn!("Failed to resolve username {} with {}: {}", username, provider.name(), e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        if let Some(entry) = self.cache.read().await.get(&key) {
+            log::warn!("Using expired cache entry for username {}", username);
+            return Ok(entry.profile.clone());
+        }
+
+        Err(error.unwrap_or_else(|| anyhow::anyhow!("No profile providers configured")))
+    }
+
+    /// Looks up the Floodgate profile of a gamertag, `None` if there is no Xbox account with that
+    /// gamertag. Not cached, as the Xbox accounts are only looked up when linking.
+    pub async fn resolve_gamertag(&self, gamertag: &str) -> anyhow::Result<Option<MojangResponse>> {
+        if !bedrock::is_valid_gamertag(gamertag) {
+            return Ok(None);
+        }
+
+        let xuid = self.bedrock.resolve_gamertag(gamertag).await
+            .with_context(|| format!("Failed to resolve gamertag {} with {}", gamertag, self.bedrock.name()))?;
+
+        Ok(xuid.map(|xuid| MojangResponse {
+            id: bedrock::floodgate_uuid(xuid),
+            name: bedrock::floodgate_name(&self.floodgate_prefix, gamertag),
+        }))
+    }
+
+    /// Looks up the current profile of a player. Not cached, as it's used to notice name changes.
+    pub async fn resolve_uuid(&self, uuid: Uuid) -> anyhow::Result<Option<MojangResponse>> {
+        let mut error = None;
+        for provider in self.providers.iter() {
+            match provider.resolve_uuid(uuid).await {
+                Ok(Some(profile)) => {
+                    self.cache_profile(profile.name.to_lowercase(), Some(profile.clone())).await;
+                    return Ok(Some(profile));
+                }
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    log::warn!("Failed to resolve UUID {} with {}: {}", uuid, provider.name(), e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        Err(error.unwrap_or_else(|| anyhow::anyhow!("No profile providers configured")))
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        let ttl = if entry.profile.is_some() { self.ttl } else { NOT_FOUND_TTL.min(self.ttl) };
+        entry.fetched_at.elapsed() >= ttl
+    }
+
+    /// Caches a lookup result. A full cache first drops its expired entries, then the oldest one.
+    async fn cache_profile(&self, key: String, profile: Option<MojangResponse>) {
+        let mut cache = self.cache.write().await;
+        if cache.len() >= MAX_CACHE_ENTRIES && !cache.contains_key(&key) {
+            cache.retain(|_, entry| !self.is_expired(entry));
+            if cache.len() >= MAX_CACHE_ENTRIES {
+                let oldest = cache.iter()
+                    .min_by_key(|(_, entry)| entry.fetched_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+
+        cache.insert(key, CacheEntry {
+            profile,
+            fetched_at: Instant::now(),
+        });
+    }
+}
+
+/// Minecraft usernames are at most 16 letters, digits and underscores.
+fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= 16
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct MojangResponse {
+    pub(crate) id: Uuid,
+    pub(crate) name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use super::*;
+
+    /// Answers every lookup with the same profile, or fails while `failing` is set.
+    struct Fixed {
+        profile: Option<MojangResponse>,
+        failing: Arc<AtomicBool>,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl Fixed {
+        fn new(profile: Option<MojangResponse>) -> (Self, Arc<AtomicBool>, Arc<AtomicUsize>) {
+            let failing = Arc::new(AtomicBool::new(false));
+            let lookups = Arc::new(AtomicUsize::new(0));
+            (Self { profile, failing: failing.clone(), lookups: lookups.clone() }, failing, lookups)
+        }
+
+        fn answer(&self) -> anyhow::Result<Option<MojangResponse>> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                anyhow::bail!("Provider is down");
+            }
+            Ok(self.profile.clone())
+        }
+    }
+
+    #[async_trait]
+    impl ProfileResolver for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn resolve_username(&self, _username: &str) -> anyhow::Result<Option<MojangResponse>> {
+            self.answer()
+        }
+
+        async fn resolve_uuid(&self, _uuid: Uuid) -> anyhow::Result<Option<MojangResponse>> {
+            self.answer()
+        }
+    }
+
+    const TTL: Duration = Duration::from_secs(60 * 60);
+
+    fn profiles(providers: Vec<Box<dyn ProfileResolver>>, ttl: Duration) -> Profiles {
+        Profiles::new(providers, Arc::new(bedrock::Stub), ".".to_string(), ttl)
+    }
+
+    fn notch() -> MojangResponse {
+        MojangResponse {
+            id: Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap(),
+            name: "Notch".to_string(),
+        }
+    }
+
+    fn ago(duration: Duration) -> Instant {
+        Instant::now().checked_sub(duration).unwrap()
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_next_provider() {
+        let (down, failing, _) = Fixed::new(None);
+        failing.store(true, Ordering::SeqCst);
+        let profiles = profiles(vec![Box::new(down), Box::new(Stub)], TTL);
+
+        let profile = profiles.resolve_username("Notch").await.unwrap().unwrap();
+        assert_eq!(profile.name, "Notch");
+    }
+
+    #[tokio::test]
+    async fn caches_lookups_ignoring_case() {
+        let (fixed, _, lookups) = Fixed::new(Some(notch()));
+        let profiles = profiles(vec![Box::new(fixed)], TTL);
+
+        profiles.resolve_username("Notch").await.unwrap();
+        let profile = profiles.resolve_username("notch").await.unwrap().unwrap();
+
+        assert_eq!(profile.id, notch().id);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn uses_expired_entries_when_all_providers_fail() {
+        let (fixed, failing, lookups) = Fixed::new(Some(notch()));
+        let profiles = profiles(vec![Box::new(fixed)], Duration::ZERO);
+
+        profiles.resolve_username("Notch").await.unwrap();
+        failing.store(true, Ordering::SeqCst);
+        let profile = profiles.resolve_username("Notch").await.unwrap().unwrap();
+
+        assert_eq!(profile.id, notch().id);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn fails_when_all_providers_fail_without_a_cache_entry() {
+        let (fixed, failing, _) = Fixed::new(Some(notch()));
+        failing.store(true, Ordering::SeqCst);
+        let profiles = profiles(vec![Box::new(fixed)], TTL);
+
+        assert!(profiles.resolve_username("Notch").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn expires_unknown_names_sooner() {
+        let (fixed, _, lookups) = Fixed::new(Some(notch()));
+        let profiles = profiles(vec![Box::new(fixed)], TTL);
+        {
+            let mut cache = profiles.cache.write().await;
+            cache.insert("notch".to_string(), CacheEntry { profile: Some(notch()), fetched_at: ago(NOT_FOUND_TTL * 2) });
+            cache.insert("ghost".to_string(), CacheEntry { profile: None, fetched_at: ago(NOT_FOUND_TTL * 2) });
+        }
+
+        profiles.resolve_username("Notch").await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 0);
+
+        profiles.resolve_username("Ghost").await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn evicts_expired_entries_then_the_oldest() {
+        let profiles = profiles(vec![Box::new(Stub)], TTL);
+        {
+            let mut cache = profiles.cache.write().await;
+            for i in 0..MAX_CACHE_ENTRIES - 2 {
+                cache.insert(format!("player{i}"), CacheEntry { profile: Some(notch()), fetched_at: Instant::now() });
+            }
+            cache.insert("oldest".to_string(), CacheEntry { profile: Some(notch()), fetched_at: ago(TTL / 2) });
+            cache.insert("expired".to_string(), CacheEntry { profile: Some(notch()), fetched_at: ago(TTL * 2) });
+        }
+
+        profiles.resolve_username("first").await.unwrap();
+        {
+            let cache = profiles.cache.read().await;
+            assert_eq!(cache.len(), MAX_CACHE_ENTRIES);
+            assert!(!cache.contains_key("expired"));
+            assert!(cache.contains_key("oldest"));
+        }
+
+        profiles.resolve_username("second").await.unwrap();
+        let cache = profiles.cache.read().await;
+        assert_eq!(cache.len(), MAX_CACHE_ENTRIES);
+        assert!(!cache.contains_key("oldest"));
+        assert!(cache.contains_key("first") && cache.contains_key("second"));
+    }
+
+    #[tokio::test]
+    async fn resolves_gamertags_to_floodgate_profiles() {
+        let profiles = profiles(vec![Box::new(Stub)], TTL);
+
+        let profile = profiles.resolve("Some Gamer", Edition::Bedrock).await.unwrap().unwrap();
+        assert_eq!(bedrock::edition(profile.id), Edition::Bedrock);
+        assert_eq!(profile.name, ".Some_Gamer");
+    }
+}
@@ -0,0 +1,170 @@
+use std::time::Duration;
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::mojang::{MojangResponse, ProfileResolver};
+
+pub(super) const MOJANG_URL: &str = "https://api.mojang.com/users/profiles/minecraft";
+pub(super) const SESSION_SERVER_URL: &str = "https://sessionserver.mojang.com/session/minecraft/profile";
+const PLAYERDB_URL: &str = "https://playerdb.co/api/player/minecraft";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const USER_AGENT: &str = concat!("winterjam-mc-link/", env!("CARGO_PKG_VERSION"));
+
+pub(super) fn client() -> Client {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+/// The Mojang API, or a mirror with the same interface such as Crafthead.
+pub(super) struct Mojang {
+    client: Client,
+    base_url: String,
+    /// Profiles by UUID are served by the session server rather than the API.
+    profile_url: String,
+}
+
+impl Mojang {
+    pub fn new(base_url: String, profile_url: String) -> Self {
+        Self {
+            client: client(),
+            base_url,
+            profile_url,
+        }
+    }
+
+    async fn get(&self, url: String) -> anyhow::Result<Option<MojangResponse>> {
+        let response = self.client.get(url).send().await
+            .context("Failed to resolve profile")?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::NO_CONTENT => return Ok(None),
+            status if !status.is_success() => {
+                anyhow::bail!("Failed to resolve profile - {}: {:?}", status, response.text().await);
+            }
+            _ => {}
+        }
+
+        let value = response.json::<MojangResponse>().await
+            .context("Failed to parse response")?;
+
+        Ok(Some(value))
+    }
+}
+
+#[async_trait]
+impl ProfileResolver for Mojang {
+    fn name(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn resolve_username(&self, username: &str) -> anyhow::Result<Option<MojangResponse>> {
+        self.get(format!("{}/{username}", self.base_url.trim_end_matches('/'))).await
+    }
+
+    async fn resolve_uuid(&self, uuid: Uuid) -> anyhow::Result<Option<MojangResponse>> {
+        self.get(format!("{}/{}", self.profile_url.trim_end_matches('/'), uuid.simple())).await
+    }
+}
+
+/// The PlayerDB API, which answers from its own cache when Mojang is unavailable.
+pub(super) struct PlayerDb {
+    client: Client,
+}
+
+impl PlayerDb {
+    pub fn new() -> Self {
+        Self {
+            client: client(),
+        }
+    }
+
+    /// PlayerDB takes either a username or a UUID.
+    async fn get(&self, player: &str) -> anyhow::Result<Option<MojangResponse>> {
+        let response = self.client.get(format!("{PLAYERDB_URL}/{player}")).send().await
+            .context("Failed to resolve profile")?;
+
+        // unknown players are reported as client errors
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            anyhow::bail!("Failed to resolve profile - {}: {:?}", status, response.text().await);
+        }
+
+        let value = response.json::<PlayerDbResponse>().await
+            .context("Failed to parse response")?;
+
+        match value.data.and_then(|data| data.player) {
+            Some(player) if value.success => Ok(Some(MojangResponse {
+                id: player.id,
+                name: player.username,
+            })),
+            _ if status.is_client_error() => Ok(None),
+            _ => anyhow::bail!("Failed to resolve profile: {}", value.code),
+        }
+    }
+}
+
+#[async_trait]
+impl ProfileResolver for PlayerDb {
+    fn name(&self) -> &str {
+        "PlayerDB"
+    }
+
+    async fn resolve_username(&self, username: &str) -> anyhow::Result<Option<MojangResponse>> {
+        self.get(username).await
+    }
+
+    async fn resolve_uuid(&self, uuid: Uuid) -> anyhow::Result<Option<MojangResponse>> {
+        self.get(&uuid.to_string()).await
+    }
+}
+
+#[derive(Deserialize)]
+struct PlayerDbResponse {
+    code: String,
+    success: bool,
+    data: Option<PlayerDbData>,
+}
+
+#[derive(Deserialize)]
+struct PlayerDbData {
+    player: Option<PlayerDbPlayer>,
+}
+
+#[derive(Deserialize)]
+struct PlayerDbPlayer {
+    id: Uuid,
+    username: String,
+}
+
+/// Makes up a profile for every username, with a UUID derived from the name, for development
+/// without network access.
+pub(super) struct Stub;
+
+#[async_trait]
+impl ProfileResolver for Stub {
+    fn name(&self) -> &str {
+        "stub"
+    }
+
+    async fn resolve_username(&self, username: &str) -> anyhow::Result<Option<MojangResponse>> {
+        let hash = Sha256::digest(username.to_lowercase().as_bytes());
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hash[..16]);
+
+        Ok(Some(MojangResponse {
+            id: uuid::Builder::from_random_bytes(bytes).into_uuid(),
+            name: username.to_string(),
+        }))
+    }
+
+    /// The stub can't know the name belonging to a UUID.
+    async fn resolve_uuid(&self, _uuid: Uuid) -> anyhow::Result<Option<MojangResponse>> {
+        Ok(None)
+    }
+}
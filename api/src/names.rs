@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use serde::Serialize;
+use tokio::sync::{Mutex, Notify};
+use uuid::Uuid;
+use entity::prelude::User;
+use entity::user;
+use entity::user::Edition;
+use rusty_interaction::types::Snowflake;
+use crate::discord::{outbox, webhook};
+use crate::mojang::Profiles;
+
+/// Pause between profile lookups, to stay well below the rate limits of the profile providers.
+const LOOKUP_INTERVAL: Duration = Duration::from_millis(500);
+const PAGE_SIZE: u64 = 100;
+
+/// Keeps the stored Minecraft usernames of linked players up to date, as players can rename
+/// their accounts at any time.
+pub(crate) struct NameRefresh {
+    trigger: Notify,
+    /// Whether the triggered refresh covers all accounts or only those without a name.
+    full: AtomicBool,
+    status: Mutex<RefreshStatus>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub(crate) struct RefreshStatus {
+    pub running: bool,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    /// Counts of the last or currently running refresh. Accounts whose profile lookup or rename
+    /// failed are counted as failed and retried by the next refresh.
+    pub checked: u64,
+    pub renamed: u64,
+    pub failed: u64,
+}
+
+impl NameRefresh {
+    pub fn new() -> Self {
+        Self {
+            trigger: Notify::new(),
+            full: AtomicBool::new(false),
+            status: Mutex::new(RefreshStatus::default()),
+        }
+    }
+
+    /// Starts a refresh, or another one right after the running one.
+    pub fn trigger(&self) {
+        self.full.store(true, Ordering::Release);
+        self.trigger.notify_one();
+    }
+
+    /// Starts looking up the names of accounts without one, e.g. after linking an account whose
+    /// name couldn't be resolved right away.
+    pub fn trigger_unnamed(&self) {
+        self.trigger.notify_one();
+    }
+
+    pub async fn status(&self) -> RefreshStatus {
+        self.status.lock().await.clone()
+    }
+
+    /// Looks up the names of all Java accounts, or only of those without a stored name if
+    /// `unnamed_only` is set.
+    async fn refresh_all(&self, db: &DatabaseConnection, profiles: &Profiles, unnamed_only: bool) -> Result<(), DbErr> {
+        // the same account can be linked in several events, but only needs to be looked up once
+        let mut names: HashMap<Uuid, Option<String>> = HashMap::new();
+
+        // the profile providers only know Java accounts
+        let mut select = User::find().filter(user::Column::Edition.eq(Edition::Java));
+        if unnamed_only {
+            select = select.filter(user::Column::MinecraftName.is_null());
+        }
+        let mut pages = select
+            .order_by_asc(user::Column::Id)
+            .paginate(db, PAGE_SIZE);
+        while let Some(users) = pages.fetch_and_next().await? {
+            for user in users {
+                let name = match names.get(&user.minecraft_uuid) {
+                    Some(name) => name.clone(),
+                    None => {
+                        tokio::time::sleep(LOOKUP_INTERVAL).await;
+                        match profiles.resolve_uuid(user.minecraft_uuid).await {
+                            Ok(profile) => {
+                                let name = profile.map(|profile| profile.name);
+                                names.insert(user.minecraft_uuid, name.clone());
+                                name
+                            }
+                            Err(e) => {
+                                log::warn!("Failed to resolve profile of {}: {}", user.minecraft_uuid, e);
+                                self.status.lock().await.failed += 1;
+                                continue;
+                            }
+                        }
+                    }
+                };
+                self.status.lock().await.checked += 1;
+
+                // keep the stored name if the player can't be found
+                let name = match name {
+                    Some(name) if user.minecraft_name.as_ref() != Some(&name) => name,
+                    _ => continue,
+                };
+
+                let uuid = user.minecraft_uuid;
+                match rename(db, user, name).await {
+                    Ok(()) => self.status.lock().await.renamed += 1,
+                    Err(e) => {
+                        log::error!("Error renaming Minecraft account {} in DB: {}", uuid, e);
+                        self.status.lock().await.failed += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run(&self, db: &DatabaseConnection, profiles: &Profiles, unnamed_only: bool) {
+        log::info!("Refreshing Minecraft usernames");
+        {
+            let mut status = self.status.lock().await;
+            *status = RefreshStatus {
+                running: true,
+                last_started_at: Some(Utc::now()),
+                last_finished_at: status.last_finished_at,
+                ..Default::default()
+            };
+        }
+
+        if let Err(e) = self.refresh_all(db, profiles, unnamed_only).await {
+            log::error!("Error refreshing Minecraft usernames: {}", e);
+        }
+
+        let mut status = self.status.lock().await;
+        status.running = false;
+        status.last_finished_at = Some(Utc::now());
+        log::info!("Refreshed Minecraft usernames: {} checked, {} renamed, {} failed", status.checked, status.renamed, status.failed);
+    }
+}
+
+/// Refreshes the names every `interval` and whenever triggered, forever. Links made before names
+/// were stored are filled in right away, as the whitelist exports need their names.
+pub(crate) async fn keep_fresh(refresh: Data<NameRefresh>, db: DatabaseConnection, profiles: Profiles, interval: Option<Duration>) {
+    refresh.run(&db, &profiles, true).await;
+
+    loop {
+        match interval {
+            Some(interval) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => refresh.full.store(true, Ordering::Release),
+                    _ = refresh.trigger.notified() => {}
+                }
+            }
+            None => refresh.trigger.notified().await,
+        }
+
+        let unnamed_only = !refresh.full.swap(false, Ordering::AcqRel);
+        refresh.run(&db, &profiles, unnamed_only).await;
+    }
+}
+
+async fn rename(db: &DatabaseConnection, user: user::Model, name: String) -> Result<(), DbErr> {
+    log::info!("Minecraft account {} of user {} was renamed from {:?} to {}", user.minecraft_uuid, user.discord_snowflake, user.minecraft_name, name);
+
+    db.transaction::<_, (), DbErr>(|txn| {
+        Box::pin(async move {
+            // filling in the name of an old link is no rename worth announcing
+            let message = user.minecraft_name.as_deref()
+                .map(|old| webhook::name_change_message(user.discord_snowflake as Snowflake, Some(old), &name, user.minecraft_uuid));
+            let event_id = user.event_id;
+
+            let mut user: user::ActiveModel = user.into();
+            user.minecraft_name = Set(Some(name));
+            user.updated_at = Set(Utc::now().into());
+            user.update(txn).await?;
+
+            match message {
+                Some(message) => outbox::enqueue(txn, event_id, &message).await,
+                None => Ok(()),
+            }
+        })
+    }).await.map_err(|e| match e {
+        sea_orm::TransactionError::Connection(e) => e,
+        sea_orm::TransactionError::Transaction(e) => e,
+    })
+}
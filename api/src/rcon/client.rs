@@ -0,0 +1,212 @@
+use std::time::Duration;
+use anyhow::Context;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const PACKET_AUTH: i32 = 3;
+const PACKET_AUTH_RESPONSE: i32 = 2;
+const PACKET_EXEC_COMMAND: i32 = 2;
+
+/// The vanilla server rejects packets larger than this.
+const MAX_PAYLOAD_SIZE: usize = 1446;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimal client for the Source RCON protocol as implemented by the vanilla Minecraft server.
+pub(crate) struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    pub async fn connect(address: &str, password: &str) -> anyhow::Result<Self> {
+        let stream = timeout(IO_TIMEOUT, TcpStream::connect(address)).await
+            .context("Timed out connecting to RCON server")?
+            .context("Failed to connect to RCON server")?;
+
+        let mut client = Self {
+            stream,
+            next_id: 0,
+        };
+
+        let id = client.send_packet(PACKET_AUTH, password).await?;
+        loop {
+            let (response_id, kind, _) = client.read_packet().await?;
+            if kind != PACKET_AUTH_RESPONSE {
+                continue;
+            }
+            if response_id == -1 {
+                anyhow::bail!("RCON authentication failed for {address}");
+            }
+            if response_id != id {
+                anyhow::bail!("Unexpected RCON auth response id {response_id}, expected {id}");
+            }
+            break;
+        }
+
+        Ok(client)
+    }
+
+    pub async fn command(&mut self, command: &str) -> anyhow::Result<String> {
+        let id = self.send_packet(PACKET_EXEC_COMMAND, command).await?;
+        let (response_id, _, body) = self.read_packet().await?;
+        if response_id != id {
+            anyhow::bail!("Unexpected RCON response id {response_id}, expected {id}");
+        }
+
+        Ok(body)
+    }
+
+    async fn send_packet(&mut self, kind: i32, body: &str) -> anyhow::Result<i32> {
+        if body.len() > MAX_PAYLOAD_SIZE {
+            anyhow::bail!("RCON payload too large: {} bytes", body.len());
+        }
+
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        let id = self.next_id;
+
+        let mut packet = Vec::with_capacity(body.len() + 14);
+        packet.extend_from_slice(&((body.len() + 10) as i32).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+
+        timeout(IO_TIMEOUT, self.stream.write_all(&packet)).await
+            .context("Timed out writing RCON packet")?
+            .context("Failed to write RCON packet")?;
+
+        Ok(id)
+    }
+
+    async fn read_packet(&mut self) -> anyhow::Result<(i32, i32, String)> {
+        let mut length = [0u8; 4];
+        timeout(IO_TIMEOUT, self.stream.read_exact(&mut length)).await
+            .context("Timed out reading RCON packet")?
+            .context("Failed to read RCON packet")?;
+
+        let length = i32::from_le_bytes(length);
+        if !(10..=4096 + 10).contains(&length) {
+            anyhow::bail!("Invalid RCON packet length {length}");
+        }
+
+        let mut payload = vec![0u8; length as usize];
+        timeout(IO_TIMEOUT, self.stream.read_exact(&mut payload)).await
+            .context("Timed out reading RCON packet")?
+            .context("Failed to read RCON packet")?;
+
+        let id = i32::from_le_bytes(payload[0..4].try_into()?);
+        let kind = i32::from_le_bytes(payload[4..8].try_into()?);
+        let body = String::from_utf8_lossy(&payload[8..payload.len() - 2]).into_owned();
+
+        Ok((id, kind, body))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use tokio::net::TcpListener;
+    use super::*;
+
+    /// Reads a packet like the vanilla server, checking its framing.
+    pub(in crate::rcon) async fn read_packet(stream: &mut TcpStream) -> (i32, i32, String) {
+        let length = stream.read_i32_le().await.unwrap();
+        let mut payload = vec![0u8; length as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        assert_eq!(payload[payload.len() - 2..], [0, 0], "RCON packets end with two null bytes");
+
+        let id = i32::from_le_bytes(payload[0..4].try_into().unwrap());
+        let kind = i32::from_le_bytes(payload[4..8].try_into().unwrap());
+        let body = String::from_utf8(payload[8..payload.len() - 2].to_vec()).unwrap();
+        (id, kind, body)
+    }
+
+    pub(in crate::rcon) async fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &str) {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&((body.len() + 10) as i32).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    /// Accepts a connection and expects it to log in with `password`.
+    pub(in crate::rcon) async fn accept(listener: &TcpListener, password: &str) -> TcpStream {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (id, kind, body) = read_packet(&mut stream).await;
+        assert_eq!(kind, PACKET_AUTH);
+        assert_eq!(body, password);
+        write_packet(&mut stream, id, PACKET_AUTH_RESPONSE, "").await;
+        stream
+    }
+
+    #[tokio::test]
+    async fn sends_commands_after_login() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let mut stream = accept(&listener, "secret").await;
+            let mut ids = Vec::new();
+            for command in ["whitelist add Notch", "op Notch"] {
+                let (id, kind, body) = read_packet(&mut stream).await;
+                assert_eq!(kind, PACKET_EXEC_COMMAND);
+                assert_eq!(body, command);
+                write_packet(&mut stream, id, 0, &format!("Ran {body}")).await;
+                ids.push(id);
+            }
+            ids
+        });
+
+        let mut client = RconClient::connect(&address, "secret").await.unwrap();
+        assert_eq!(client.command("whitelist add Notch").await.unwrap(), "Ran whitelist add Notch");
+        assert_eq!(client.command("op Notch").await.unwrap(), "Ran op Notch");
+
+        // the login used the first id
+        assert_eq!(server.await.unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn fails_with_wrong_password() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_packet(&mut stream).await;
+            write_packet(&mut stream, -1, PACKET_AUTH_RESPONSE, "").await;
+        });
+
+        let Err(e) = RconClient::connect(&address, "wrong").await else {
+            panic!("Logged in with the wrong password");
+        };
+        assert!(e.to_string().contains("authentication failed"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn rejects_responses_to_other_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut stream = accept(&listener, "secret").await;
+            let (id, _, _) = read_packet(&mut stream).await;
+            write_packet(&mut stream, id + 1, 0, "").await;
+        });
+
+        let mut client = RconClient::connect(&address, "secret").await.unwrap();
+        assert!(client.command("list").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let _stream = accept(&listener, "secret").await;
+            tokio::time::sleep(IO_TIMEOUT).await;
+        });
+
+        let mut client = RconClient::connect(&address, "secret").await.unwrap();
+        assert!(client.command(&"a".repeat(MAX_PAYLOAD_SIZE + 1)).await.is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::net::Ipv6Addr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use entity::{event, rcon_server, user};
+use entity::user::Edition;
+use entity::prelude::RconServer as RconServerEntity;
+use rusty_interaction::types::Snowflake;
+use client::RconClient;
+use crate::config::Redacted;
+
+mod client;
+
+const DEFAULT_PORT: u16 = 25575;
+const MAX_ATTEMPTS: u32 = 10;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+#[derive(Clone)]
+pub(crate) struct RconServer {
+    pub address: String,
+    password: String,
+}
+
+impl From<rcon_server::Model> for RconServer {
+    fn from(server: rcon_server::Model) -> Self {
+        Self {
+            address: server.address,
+            password: server.password,
+        }
+    }
+}
+
+impl Debug for RconServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RconServer")
+            .field("address", &self.address)
+            .field("password", &Redacted)
+            .finish()
+    }
+}
+
+impl RconServer {
+    /// Validates the address, given as `host[:port]` with IPv6 hosts in brackets, and adds the
+    /// default port if it has none.
+    pub fn new(address: &str, password: String) -> anyhow::Result<Self> {
+        Ok(Self {
+            address: parse_address(address)?,
+            password,
+        })
+    }
+
+    /// Parses a server definition in the form `password@host[:port]`.
+    pub fn parse(definition: &str) -> anyhow::Result<Self> {
+        let (password, address) = match definition.trim().rsplit_once('@') {
+            Some(parts) => parts,
+            None => anyhow::bail!("RCON server definition is missing a password: {definition}"),
+        };
+
+        Self::new(address, password.to_string())
+    }
+}
+
+fn parse_address(address: &str) -> anyhow::Result<String> {
+    let address = address.trim();
+    if address.is_empty() {
+        anyhow::bail!("RCON server definition is missing an address");
+    }
+
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => anyhow::bail!("Invalid RCON address {address}"),
+            },
+            None => anyhow::bail!("Invalid RCON address {address}"),
+        },
+        None => match address.rsplit_once(':') {
+            // `::1` would otherwise be read as host `:` and port 1
+            Some((host, _)) if host.contains(':') => anyhow::bail!("IPv6 RCON addresses must be enclosed in brackets: {address}"),
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    if host.is_empty() {
+        anyhow::bail!("RCON address is missing a host: {address}");
+    }
+    if address.starts_with('[') && host.parse::<Ipv6Addr>().is_err() {
+        anyhow::bail!("Invalid IPv6 address in RCON address {address}");
+    }
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| anyhow::anyhow!("Invalid RCON port in {address}"))?,
+        None => DEFAULT_PORT,
+    };
+
+    Ok(if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    })
+}
+
+/// Pushes console commands to the Minecraft servers of an event.
+///
+/// Servers are added to events through the admin API, the ones from the configuration belong to
+/// the event of the configured guild. Every server gets its own queue that is worked off in order,
+/// so a failed command is retried before anything queued after it is sent.
+#[derive(Clone)]
+pub(crate) struct Rcon {
+    db: DatabaseConnection,
+    guild_id: Option<Snowflake>,
+    configured: Vec<RconServer>,
+    queues: Arc<Mutex<HashMap<(String, String), UnboundedSender<Vec<String>>>>>,
+}
+
+impl Rcon {
+    pub fn new(db: DatabaseConnection, guild_id: Option<Snowflake>, configured: Vec<RconServer>) -> Self {
+        Self {
+            db,
+            guild_id,
+            configured,
+            queues: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Queues the console commands for a link change: `old` is removed and `new` added, along
+    /// with their operator permissions if the user is a moderator.
+    pub async fn push_change(&self, event: &event::Model, old: Option<&user::Model>, new: Option<&user::Model>, operator: bool) {
+        let mut commands = Vec::new();
+        if let Some(old) = old {
+            if operator {
+                commands.extend(old.minecraft_name.as_ref().map(|name| format!("deop {name}")));
+            }
+            commands.extend(whitelist_command("remove", old));
+        }
+        if let Some(new) = new {
+            commands.extend(whitelist_command("add", new));
+            if operator {
+                commands.extend(new.minecraft_name.as_ref().map(|name| format!("op {name}")));
+            }
+        }
+        self.push(event, commands).await;
+    }
+
+    /// Queues the console command that grants or revokes operator permissions.
+    pub async fn push_operator(&self, event: &event::Model, user: &user::Model, operator: bool) {
+        let action = if operator { "op" } else { "deop" };
+        self.push(event, user.minecraft_name.iter().map(|name| format!("{action} {name}")).collect()).await;
+    }
+
+    pub async fn push(&self, event: &event::Model, commands: Vec<String>) {
+        if commands.is_empty() {
+            return;
+        }
+
+        let result = RconServerEntity::find().filter(rcon_server::Column::EventId.eq(event.id)).all(&self.db).await;
+        let mut servers: Vec<RconServer> = match result {
+            Ok(servers) => servers.into_iter().map(RconServer::from).collect(),
+            Err(e) => {
+                log::error!("Error getting RCON servers from DB, dropping commands {:?}: {}", commands, e);
+                return;
+            }
+        };
+        if self.guild_id == Some(event.guild_id as Snowflake) {
+            servers.extend(self.configured.iter().cloned());
+        }
+
+        let mut queues = self.queues.lock().unwrap();
+        for server in servers {
+            let queue = queues.entry((server.address.clone(), server.password.clone())).or_insert_with(|| {
+                log::info!("Pushing whitelist changes to {} over RCON", server.address);
+
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(run(server, receiver, RETRY_DELAY));
+                sender
+            });
+
+            if let Err(e) = queue.send(commands.clone()) {
+                log::error!("RCON queue closed, dropping commands: {:?}", e.0);
+            }
+        }
+    }
+}
+
+/// Bedrock players are unknown to Mojang, so they are whitelisted by UUID through Floodgate.
+fn whitelist_command(action: &str, user: &user::Model) -> Option<String> {
+    match user.edition {
+        Edition::Java => user.minecraft_name.as_ref().map(|name| format!("whitelist {action} {name}")),
+        Edition::Bedrock => Some(format!("fwhitelist {action} {}", user.minecraft_uuid)),
+    }
+}
+
+async fn run(server: RconServer, mut receiver: UnboundedReceiver<Vec<String>>, retry_delay: Duration) {
+    let mut connection: Option<RconClient> = None;
+
+    while let Some(commands) = receiver.recv().await {
+        let mut attempt = 0;
+        loop {
+            match execute(&server, &mut connection, &commands).await {
+                Ok(_) => break,
+                Err(e) => {
+                    connection = None;
+                    attempt += 1;
+
+                    if attempt >= MAX_ATTEMPTS {
+                        log::error!("Giving up on RCON commands for {} after {} attempts: {:?} - {}", server.address, attempt, commands, e);
+                        break;
+                    }
+
+                    let delay = retry_delay.saturating_mul(2u32.saturating_pow(attempt - 1)).min(MAX_RETRY_DELAY);
+                    log::warn!("Failed to send RCON commands to {}, retrying in {}s: {}", server.address, delay.as_secs(), e);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+async fn execute(server: &RconServer, connection: &mut Option<RconClient>, commands: &[String]) -> anyhow::Result<()> {
+    if connection.is_none() {
+        *connection = Some(RconClient::connect(&server.address, &server.password).await?);
+    }
+    let client = connection.as_mut().unwrap();
+
+    for command in commands {
+        let response = client.command(command).await?;
+        log::info!("RCON {} > {}: {}", server.address, command, response);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use super::*;
+    use super::client::tests::{accept, read_packet, write_packet};
+
+    #[test]
+    fn parses_definitions() {
+        let server = RconServer::parse("secret@localhost").unwrap();
+        assert_eq!(server.address, "localhost:25575");
+        assert_eq!(server.password, "secret");
+
+        assert_eq!(RconServer::parse("secret@127.0.0.1:25580").unwrap().address, "127.0.0.1:25580");
+        assert_eq!(RconServer::parse("secret@[::1]").unwrap().address, "[::1]:25575");
+        assert_eq!(RconServer::parse("secret@[::1]:25580").unwrap().address, "[::1]:25580");
+        assert_eq!(RconServer::parse("se@cret@localhost").unwrap().password, "se@cret");
+    }
+
+    #[test]
+    fn rejects_invalid_definitions() {
+        for definition in ["localhost", "secret@", "secret@::1", "secret@::1:25575", "secret@:25575", "secret@localhost:rcon", "secret@localhost:65536", "secret@[::1", "secret@[::1]25575", "secret@[localhost]:25575"] {
+            assert!(RconServer::parse(definition).is_err(), "{definition}");
+        }
+    }
+
+    #[test]
+    fn validates_addresses_from_the_config_file() {
+        assert_eq!(RconServer::new("localhost", "secret".to_string()).unwrap().address, "localhost:25575");
+        assert!(RconServer::new("::1", "secret".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn retries_on_a_new_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = RconServer::new(&listener.local_addr().unwrap().to_string(), "secret".to_string()).unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(server, receiver, Duration::from_millis(10)));
+
+        sender.send(vec!["whitelist add Notch".to_string()]).unwrap();
+        let mut stream = accept(&listener, "secret").await;
+        let (_, _, body) = read_packet(&mut stream).await;
+        assert_eq!(body, "whitelist add Notch");
+        drop(stream);
+
+        let mut stream = accept(&listener, "secret").await;
+        let (id, _, body) = read_packet(&mut stream).await;
+        assert_eq!(body, "whitelist add Notch");
+        write_packet(&mut stream, id, 0, "").await;
+
+        // later commands reuse the connection
+        sender.send(vec!["whitelist remove jeb_".to_string()]).unwrap();
+        let (_, _, body) = read_packet(&mut stream).await;
+        assert_eq!(body, "whitelist remove jeb_");
+    }
+}
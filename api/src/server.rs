@@ -1,76 +1,199 @@
-use std::env;
-use std::future::ready;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::sync::Mutex;
 use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Scope, web};
-use actix_web::dev::{Service, ServiceResponse};
-use actix_web::http::header::HeaderValue;
 use actix_web::middleware::Logger;
 use actix_web::web::Data;
+use anyhow::Context;
 use listenfd::ListenFd;
 use sea_orm::DatabaseConnection;
+use tokio::sync::mpsc;
+use socket2::{Domain, Protocol, Socket, Type};
 use rusty_interaction::handler::InteractionHandler;
-use crate::{api, discord, health};
+use crate::{admin, api, discord, health, history, names, webhooks};
+use crate::mojang::Profiles;
+use crate::api::stream::HistoryFeed;
+use crate::names::NameRefresh;
+use crate::rcon::Rcon;
+use crate::auth::RequireApiKey;
+use crate::config::{Config, ListenAddress};
+use crate::discord::{members, outbox};
+use crate::discord::members::MemberCache;
+use crate::discord::rest::DiscordClient;
 use crate::status::err_not_found;
 
-static mut API_KEY: Option<HeaderValue> = None;
-
-fn admin_api_key() -> &'static Option<HeaderValue> {
-    unsafe {
-        return &API_KEY;
-    }
-}
-
-pub async fn server_main(db: DatabaseConnection) -> anyhow::Result<()> {
-    unsafe {
-        API_KEY = env::var("API_KEY").ok().map(|key| HeaderValue::from_str(key.as_str()).ok()).flatten();
-    }
-
-    let discord_handler = discord::init(db.clone()).await?;
+pub async fn server_main(db: DatabaseConnection, config: Config) -> anyhow::Result<()> {
+    let mut discord_handler = discord::init(db.clone(), &config).await?;
+    let listen = config.listen.clone();
+    let client = discord_handler.data.get::<DiscordClient>().expect("Failed to get Discord client").clone();
+    let (changes, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(outbox::deliver_pending(db.clone(), client.clone()));
+    let members = Data::new(MemberCache::new(client, config.discord.member_cache_ttl).with_change_listener(changes));
+    discord_handler.data.insert(members.clone());
+    tokio::spawn(history::record_role_changes(db.clone(), receiver));
+    tokio::spawn(members::keep_fresh(members.clone(), db.clone()));
+    tokio::spawn(webhooks::deliver_pending(db.clone()));
+    let profiles = discord_handler.data.get::<Profiles>().expect("Failed to get profile resolver").clone();
+    let name_refresh = Data::new(NameRefresh::new());
+    tokio::spawn(names::keep_fresh(name_refresh.clone(), db.clone(), profiles.clone(), config.profiles.refresh_interval));
+    let profiles = Data::new(profiles);
+    let rcon = Data::new(discord_handler.data.get::<Rcon>().expect("Failed to get RCON").clone());
+    let history_feed = HistoryFeed::start(db.clone()).await?;
+    let config = Data::new(config);
 
     let mut listen_fd = ListenFd::from_env();
     let mut server = HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
             .app_data(Data::new(db.clone()))
-            .app_data(Data::new(discord_handler.client().clone()))
+            .app_data(members.clone())
+            .app_data(name_refresh.clone())
+            .app_data(profiles.clone())
+            .app_data(rcon.clone())
+            .app_data(history_feed.clone())
+            .app_data(config.clone())
             .default_service(web::route().to(default_route))
-            .configure(|cfg| init(cfg, &discord_handler))
+            .configure(|cfg| init(cfg, &discord_handler, &config))
     });
 
-    server = match listen_fd.take_tcp_listener(0)? {
-        Some(listener) => server.listen(listener)?,
-        None => server
-            //TODO listen on ipv6 as well
-            .bind((Ipv4Addr::UNSPECIFIED, 3000))?,
-    };
+    let mut listeners: Vec<Listener> = Vec::new();
+    if listen_fd.len() > 0 {
+        for index in 0..listen_fd.len() {
+            if let Some(listener) = listen_fd.take_tcp_listener(index)? {
+                listeners.push(Listener::Tcp(listener));
+                continue;
+            }
+
+            #[cfg(unix)]
+            if let Some(listener) = listen_fd.take_unix_listener(index)? {
+                listeners.push(Listener::Unix(listener));
+                continue;
+            }
+
+            log::warn!("Ignoring unsupported inherited socket {}", index);
+        }
+    } else {
+        match &listen {
+            Some(addresses) => {
+                for address in addresses {
+                    listeners.extend(bind(address)?);
+                }
+            }
+            None => {
+                // IPv6 may be unavailable (e.g. in containers), so only IPv4 is required by default
+                for (index, address) in ListenAddress::defaults().iter().enumerate() {
+                    match bind(address) {
+                        Ok(bound) => listeners.extend(bound),
+                        Err(e) if index > 0 => log::warn!("Not listening on {}: {}", address, e),
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    for listener in listeners {
+        server = match listener {
+            Listener::Tcp(listener) => {
+                log::info!("Listening on {}", listener.local_addr()?);
+                server.listen(listener)?
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                log::info!("Listening on unix socket {:?}", listener.local_addr()?);
+                server.listen_uds(listener)?
+            }
+        };
+    }
 
     server.run().await?;
 
     Ok(())
 }
 
-fn init(cfg: &mut web::ServiceConfig, discord_handler: &InteractionHandler) {
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+fn bind(address: &ListenAddress) -> anyhow::Result<Vec<Listener>> {
+    match address {
+        ListenAddress::Tcp(address) => {
+            Ok(tcp_listeners(address)?.into_iter().map(Listener::Tcp).collect())
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            if path.exists() {
+                std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+            }
+            let listener = UnixListener::bind(path).with_context(|| format!("Failed to bind {}", path.display()))?;
+            Ok(vec![Listener::Unix(listener)])
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => anyhow::bail!("Unix domain sockets are not supported on this platform"),
+    }
+}
+
+/// Binds every address a host resolves to. IPv6 sockets are made IPv6-only so they can share a
+/// port with an IPv4 socket for dual-stack listening.
+fn tcp_listeners(address: &str) -> anyhow::Result<Vec<TcpListener>> {
+    let addresses = address.to_socket_addrs().with_context(|| format!("Failed to resolve {address}"))?;
+
+    addresses.map(|address| -> anyhow::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+        if address.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        #[cfg(not(windows))]
+        socket.set_reuse_address(true)?;
+        socket.bind(&address.into()).with_context(|| format!("Failed to bind {address}"))?;
+        socket.listen(1024)?;
+        Ok(socket.into())
+    }).collect()
+}
+
+fn init(cfg: &mut web::ServiceConfig, discord_handler: &InteractionHandler, config: &Config) {
     cfg.service(health::healthcheck);
 
     cfg.service(
         Scope::new("/api")
-            .wrap_fn(|req, srv| {
-                if let Some(expected_key) = admin_api_key() {
-                    if let Some(provided_key) = req.headers().get("x-api-key") {
-                        if provided_key == expected_key {
-                            return srv.call(req);
-                        }
-                    }
-                }
-
-                Box::pin(ready(Ok(ServiceResponse::new(
-                    req.into_parts().0,
-                    HttpResponse::Unauthorized().finish(),
-                ))))
-            })
+            .wrap(RequireApiKey::new(config.api_key.clone()))
             .service(api::get_users)
             .service(api::get_user)
+            .service(api::get_user_by_discord)
+            .service(api::get_user_accounts_by_discord)
+            .service(api::lookup_users)
+            .service(api::get_whitelist)
+            .service(api::get_ops)
+            .service(api::stream::get_stream)
+            .service(
+                Scope::new("/admin")
+                    .service(admin::users::create_user)
+                    .service(admin::users::update_user)
+                    .service(admin::users::delete_user)
+                    .service(admin::users::force_link)
+                    .service(admin::history::get_history)
+                    .service(admin::names::get_name_refresh)
+                    .service(admin::names::trigger_name_refresh)
+                    .service(admin::notifications::get_failed_notifications)
+                    .service(admin::notifications::retry_notification)
+                    .service(admin::stats::get_member_cache_stats)
+                    .service(admin::api_keys::get_api_keys)
+                    .service(admin::api_keys::create_api_key)
+                    .service(admin::api_keys::delete_api_key)
+                    .service(admin::events::get_events)
+                    .service(admin::events::create_event)
+                    .service(admin::events::update_event)
+                    .service(admin::events::delete_event)
+                    .service(admin::webhooks::get_webhooks)
+                    .service(admin::webhooks::create_webhook)
+                    .service(admin::webhooks::delete_webhook)
+                    .service(admin::rcon::get_rcon_servers)
+                    .service(admin::rcon::create_rcon_server)
+                    .service(admin::rcon::delete_rcon_server)
+            )
     );
     let discord_data = web::Data::new(Mutex::new(discord_handler.clone()));
     cfg.service(
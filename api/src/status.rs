@@ -25,4 +25,25 @@ pub(crate) fn err_server(message: &str) -> HttpResponse {
         status: "error",
         message: Some(message),
     })
-}
\ No newline at end of file
+}
+
+pub(crate) fn err_bad_request(message: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(StatusResult {
+        status: "error",
+        message: Some(message),
+    })
+}
+
+pub(crate) fn err_forbidden(message: &str) -> HttpResponse {
+    HttpResponse::Forbidden().json(StatusResult {
+        status: "error",
+        message: Some(message),
+    })
+}
+
+pub(crate) fn err_conflict(message: &str) -> HttpResponse {
+    HttpResponse::Conflict().json(StatusResult {
+        status: "error",
+        message: Some(message),
+    })
+}
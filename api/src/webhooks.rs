@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use reqwest::Client;
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::ActiveValue::Set;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+use entity::{link_history, webhook_delivery, webhook_subscription};
+use entity::prelude::{WebhookDelivery, WebhookSubscription};
+use rusty_interaction::types::Snowflake;
+use crate::history;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BATCH_SIZE: u64 = 50;
+/// Deliveries are given up on after this many attempts, about a day with the backoff below.
+const MAX_ATTEMPTS: i32 = 30;
+const BASE_DELAY_SECONDS: i64 = 10;
+const MAX_DELAY_SECONDS: i64 = 60 * 60;
+
+/// HMAC-SHA256 of `<timestamp>.<body>` with the subscription's secret, as `sha256=<hex>`.
+const SIGNATURE_HEADER: &str = "x-mc-link-signature";
+/// Unix time of the request in seconds. It is signed along with the body, so consumers can reject
+/// old requests that were captured and replayed.
+const TIMESTAMP_HEADER: &str = "x-mc-link-timestamp";
+/// ID of the delivery, which stays the same across retries.
+const DELIVERY_HEADER: &str = "x-mc-link-delivery";
+
+/// Body of a webhook request. `id` is the ID of the history entry and increases with every change,
+/// so consumers can order changes and ignore ones they already received.
+#[derive(Serialize)]
+struct LinkChange {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub event_id: Uuid,
+    pub snowflake: Snowflake,
+    pub old_uuid: Option<Uuid>,
+    pub new_uuid: Option<Uuid>,
+    pub actor: Option<Snowflake>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Queues a history entry for delivery to every webhook subscribed to its event.
+///
+/// Called in the same transaction as the change, so a change is never lost or sent without being
+/// committed.
+pub(crate) async fn enqueue<C: ConnectionTrait>(db: &C, entry: &link_history::Model) -> Result<(), DbErr> {
+    let event_id = match entry.event_id {
+        Some(event_id) => event_id,
+        None => return Ok(()),
+    };
+
+    let subscriptions = WebhookSubscription::find()
+        .filter(webhook_subscription::Column::EventId.eq(event_id))
+        .all(db).await?;
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+
+    let payload = serde_json::to_string(&LinkChange {
+        id: entry.id,
+        kind: history::change_kind(entry.action),
+        event_id,
+        snowflake: entry.discord_snowflake as Snowflake,
+        old_uuid: entry.old_uuid,
+        new_uuid: entry.new_uuid,
+        actor: entry.actor_snowflake.map(|actor| actor as Snowflake),
+        created_at: entry.created_at,
+    }).expect("Failed to serialize link change");
+
+    for subscription in subscriptions {
+        webhook_delivery::ActiveModel {
+            subscription_id: Set(subscription.id),
+            payload: Set(payload.clone()),
+            ..Default::default()
+        }.insert(db).await?;
+    }
+
+    Ok(())
+}
+
+/// Generates a random secret for signing the requests to a subscription.
+pub(crate) fn generate_secret() -> String {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    secret.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(payload.as_bytes());
+    let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("sha256={signature}")
+}
+
+/// Sends queued deliveries that are due, forever. Failed deliveries are retried with exponential
+/// backoff until `MAX_ATTEMPTS` is reached.
+pub(crate) async fn deliver_pending(db: DatabaseConnection) {
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Failed to create HTTP client");
+
+    loop {
+        if let Err(e) = deliver_due(&db, &client).await {
+            log::error!("Error delivering webhooks: {}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn deliver_due(db: &DatabaseConnection, client: &Client) -> Result<(), DbErr> {
+    let due = WebhookDelivery::find()
+        .filter(webhook_delivery::Column::DeliveredAt.is_null())
+        .filter(webhook_delivery::Column::FailedAt.is_null())
+        .filter(webhook_delivery::Column::NextAttemptAt.lte(Utc::now()))
+        .order_by_asc(webhook_delivery::Column::Id)
+        .limit(BATCH_SIZE)
+        .find_also_related(WebhookSubscription)
+        .all(db).await?;
+
+    let mut queues = HashMap::new();
+    for (delivery, subscription) in due {
+        // deliveries are deleted together with their subscription
+        if let Some(subscription) = subscription {
+            queues.entry(subscription.id)
+                .or_insert_with(|| (subscription, Vec::new()))
+                .1.push(delivery);
+        }
+    }
+
+    // subscriptions are delivered to concurrently, so an unreachable one doesn't hold up the others
+    let mut tasks = JoinSet::new();
+    for (subscription, deliveries) in queues.into_values() {
+        tasks.spawn(deliver_to(db.clone(), client.clone(), subscription, deliveries));
+    }
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::error!("Error delivering webhooks: {}", e),
+            Err(e) => log::error!("Webhook delivery task failed: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the due deliveries of one subscription in order. After a failure the rest wait for the
+/// next poll, rather than timing out one after another.
+async fn deliver_to(db: DatabaseConnection, client: Client, subscription: webhook_subscription::Model, deliveries: Vec<webhook_delivery::Model>) -> Result<(), DbErr> {
+    for delivery in deliveries {
+        let result = send(&client, &subscription, &delivery).await;
+        let failed = result.is_err();
+
+        let attempts = delivery.attempts + 1;
+        let id = delivery.id;
+        let mut delivery: webhook_delivery::ActiveModel = delivery.into();
+        delivery.attempts = Set(attempts);
+        match result {
+            Ok(()) => {
+                delivery.delivered_at = Set(Some(Utc::now().into()));
+                delivery.last_error = Set(None);
+            }
+            Err(e) => {
+                delivery.last_error = Set(Some(e.to_string()));
+                if attempts >= MAX_ATTEMPTS {
+                    log::warn!("Giving up on webhook delivery {} to {} after {} attempts: {}", id, subscription.url, attempts, e);
+                    delivery.failed_at = Set(Some(Utc::now().into()));
+                } else {
+                    log::debug!("Webhook delivery {} to {} failed, retrying: {}", id, subscription.url, e);
+                    delivery.next_attempt_at = Set((Utc::now() + chrono::Duration::seconds(backoff_seconds(attempts))).into());
+                }
+            }
+        }
+        delivery.update(&db).await?;
+
+        if failed {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn send(client: &Client, subscription: &webhook_subscription::Model, delivery: &webhook_delivery::Model) -> anyhow::Result<()> {
+    let timestamp = Utc::now().timestamp();
+    let response = client.post(&subscription.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&subscription.secret, timestamp, &delivery.payload))
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .body(delivery.payload.clone())
+        .send().await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("Unexpected status {}", response.status());
+    }
+    Ok(())
+}
+
+/// Delay before the next attempt after `attempts` failed ones, doubling up to an hour.
+pub(crate) fn backoff_seconds(attempts: i32) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 16) as u32;
+    (BASE_DELAY_SECONDS * 2i64.pow(exponent)).min(MAX_DELAY_SECONDS)
+}
@@ -1,2 +1,9 @@
 pub mod prelude;
-pub mod user;
\ No newline at end of file
+pub mod api_key;
+pub mod event;
+pub mod link_history;
+pub mod notification_outbox;
+pub mod rcon_server;
+pub mod user;
+pub mod webhook_delivery;
+pub mod webhook_subscription;
\ No newline at end of file
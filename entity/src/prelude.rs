@@ -1,3 +1,10 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.10
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::event::Entity as Event;
+pub use super::link_history::Entity as LinkHistory;
+pub use super::notification_outbox::Entity as NotificationOutbox;
+pub use super::rcon_server::Entity as RconServer;
 pub use super::user::Entity as User;
+pub use super::webhook_delivery::Entity as WebhookDelivery;
+pub use super::webhook_subscription::Entity as WebhookSubscription;
\ No newline at end of file
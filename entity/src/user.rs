@@ -1,19 +1,135 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.10
 
+use md5::{Digest, Md5};
 use sea_orm::entity::prelude::*;
+use sea_orm::ActiveValue;
+use sea_orm::ActiveValue::Set;
+use serde::Serialize;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "user")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
-    #[sea_orm(unique)]
+    pub event_id: Uuid,
     pub discord_snowflake: i64,
-    #[sea_orm(unique)]
     pub minecraft_uuid: Uuid,
+    pub minecraft_name: Option<String>,
+    pub edition: Edition,
+    /// UUID of the player on servers in offline mode, `None` for Bedrock accounts and unknown names.
+    pub offline_uuid: Option<Uuid>,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum Edition {
+    #[sea_orm(string_value = "java")]
+    Java,
+    /// Joins through Geyser, with a Floodgate UUID derived from the Xbox XUID.
+    #[sea_orm(string_value = "bedrock")]
+    Bedrock,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::event::Entity",
+        from = "Column::EventId",
+        to = "super::event::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Event,
+}
+
+impl Related<super::event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Event.def()
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Keeps the offline UUID in line with the name.
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let (Some(edition), Some(name)) = (known(&self.edition), known(&self.minecraft_name)) {
+            let uuid = match (edition, name) {
+                (Edition::Java, Some(name)) => Some(offline_uuid(name)),
+                _ => None,
+            };
+            self.offline_uuid = Set(uuid);
+        }
 
-impl ActiveModelBehavior for ActiveModel {}
+        Ok(self)
+    }
+}
+
+/// The value of a field that was set or loaded.
+fn known<V: Into<Value>>(value: &ActiveValue<V>) -> Option<&V> {
+    match value {
+        ActiveValue::Set(value) | ActiveValue::Unchanged(value) => Some(value),
+        ActiveValue::NotSet => None,
+    }
+}
+
+/// The UUID servers in offline mode give a player, an MD5 based UUID of `OfflinePlayer:<name>`.
+pub fn offline_uuid(name: &str) -> Uuid {
+    let mut bytes: [u8; 16] = Md5::digest(format!("OfflinePlayer:{name}").as_bytes()).into();
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What Java Edition servers in offline mode give a player named Notch.
+    const NOTCH_OFFLINE_UUID: &str = "b50ad385-829d-3141-a216-7e7d7539ba7f";
+
+    #[test]
+    fn computes_offline_uuids() {
+        assert_eq!(offline_uuid("Notch"), Uuid::parse_str(NOTCH_OFFLINE_UUID).unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_offline_uuids_in_line_with_names() {
+        let db = DatabaseConnection::Disconnected;
+        let user = ActiveModel {
+            edition: Set(Edition::Java),
+            minecraft_name: Set(Some("Notch".to_string())),
+            ..Default::default()
+        };
+
+        let user = user.before_save(&db, true).await.unwrap();
+        assert_eq!(user.offline_uuid, Set(Some(Uuid::parse_str(NOTCH_OFFLINE_UUID).unwrap())));
+
+        let mut user = user;
+        user.minecraft_name = Set(None);
+        let user = user.before_save(&db, false).await.unwrap();
+        assert_eq!(user.offline_uuid, Set(None));
+
+        let mut user = user;
+        user.edition = Set(Edition::Bedrock);
+        user.minecraft_name = Set(Some(".Notch".to_string()));
+        let user = user.before_save(&db, false).await.unwrap();
+        assert_eq!(user.offline_uuid, Set(None));
+    }
+
+    #[tokio::test]
+    async fn leaves_offline_uuids_alone_without_the_name() {
+        let user = ActiveModel {
+            edition: Set(Edition::Java),
+            ..Default::default()
+        };
+
+        let user = user.before_save(&DatabaseConnection::Disconnected, false).await.unwrap();
+        assert!(user.offline_uuid.is_not_set());
+    }
+}
@@ -1,12 +1,36 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_users_table;
+mod m20231226_000001_add_minecraft_name;
+mod m20231227_000001_add_user_timestamps;
+mod m20231228_000001_create_link_history_table;
+mod m20231229_000001_create_api_key_table;
+mod m20231230_000001_create_event_table;
+mod m20231231_000001_create_webhook_tables;
+mod m20240101_000001_create_notification_outbox_table;
+mod m20240102_000001_add_user_edition;
+mod m20240103_000001_add_user_offline_uuid;
+mod m20240104_000001_allow_multiple_accounts;
+mod m20240105_000001_create_rcon_server_table;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_users_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_users_table::Migration),
+            Box::new(m20231226_000001_add_minecraft_name::Migration),
+            Box::new(m20231227_000001_add_user_timestamps::Migration),
+            Box::new(m20231228_000001_create_link_history_table::Migration),
+            Box::new(m20231229_000001_create_api_key_table::Migration),
+            Box::new(m20231230_000001_create_event_table::Migration),
+            Box::new(m20231231_000001_create_webhook_tables::Migration),
+            Box::new(m20240101_000001_create_notification_outbox_table::Migration),
+            Box::new(m20240102_000001_add_user_edition::Migration),
+            Box::new(m20240103_000001_add_user_offline_uuid::Migration),
+            Box::new(m20240104_000001_allow_multiple_accounts::Migration),
+            Box::new(m20240105_000001_create_rcon_server_table::Migration),
+        ]
     }
 }
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LinkHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LinkHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::DiscordSnowflake)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::ActorSnowflake)
+                            .big_integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::Action)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::OldUuid)
+                            .uuid()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::NewUuid)
+                            .uuid()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LinkHistory::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(LinkHistory::Table)
+                .name("link_history_by_discord_snowflake")
+                .col(LinkHistory::DiscordSnowflake)
+                .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(LinkHistory::Table)
+                .name("link_history_by_old_uuid")
+                .col(LinkHistory::OldUuid)
+                .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(LinkHistory::Table)
+                .name("link_history_by_new_uuid")
+                .col(LinkHistory::NewUuid)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LinkHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum LinkHistory {
+    Table,
+    Id,
+    #[sea_orm(iden = "discord_snowflake")]
+    DiscordSnowflake,
+    #[sea_orm(iden = "actor_snowflake")]
+    ActorSnowflake,
+    Action,
+    #[sea_orm(iden = "old_uuid")]
+    OldUuid,
+    #[sea_orm(iden = "new_uuid")]
+    NewUuid,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
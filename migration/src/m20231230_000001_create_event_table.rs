@@ -0,0 +1,222 @@
+use sea_orm_migration::prelude::*;
+
+/// Guild ID of the event existing links are moved to. The application assigns it the configured
+/// guild on startup.
+const PLACEHOLDER_GUILD_ID: i64 = 0;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Event::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Event::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("uuid_generate_v4()")),
+                    )
+                    .col(
+                        ColumnDef::new(Event::Name)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Event::GuildId)
+                            .big_integer()
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(Event::ModeratorRoles)
+                            .string()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Event::WebhookUrl)
+                            .string()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Event::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .exec_stmt(Query::insert()
+                .into_table(Event::Table)
+                .columns([Event::Name, Event::GuildId])
+                .values_panic(["default".into(), PLACEHOLDER_GUILD_ID.into()])
+                .to_owned()
+            ).await?;
+
+        let default_event = format!("(SELECT id FROM event WHERE guild_id = {PLACEHOLDER_GUILD_ID})");
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(User::EventId)
+                            .uuid()
+                            .null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared(&format!("UPDATE \"user\" SET event_id = {default_event};")).await?;
+        db.execute_unprepared("ALTER TABLE \"user\" ALTER COLUMN event_id SET NOT NULL;").await?;
+        // links are unique per event instead of globally
+        db.execute_unprepared("ALTER TABLE \"user\" DROP CONSTRAINT IF EXISTS user_discord_snowflake_key;").await?;
+        db.execute_unprepared("ALTER TABLE \"user\" DROP CONSTRAINT IF EXISTS user_minecraft_uuid_key;").await?;
+
+        manager
+            .create_foreign_key(ForeignKey::create()
+                .name("user_event_id_fkey")
+                .from(User::Table, User::EventId)
+                .to(Event::Table, Event::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(User::Table)
+                .name("user_event_discord_snowflake_key")
+                .col(User::EventId)
+                .col(User::DiscordSnowflake)
+                .unique()
+                .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(User::Table)
+                .name("user_event_minecraft_uuid_key")
+                .col(User::EventId)
+                .col(User::MinecraftUuid)
+                .unique()
+                .to_owned()
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(LinkHistory::Table)
+                    .add_column(
+                        ColumnDef::new(LinkHistory::EventId)
+                            .uuid()
+                            .null()
+                    )
+                    .to_owned()
+            ).await?;
+        db.execute_unprepared(&format!("UPDATE link_history SET event_id = {default_event};")).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiKey::Table)
+                    .add_column(
+                        ColumnDef::new(ApiKey::EventId)
+                            .uuid()
+                            .null()
+                    )
+                    .add_foreign_key(TableForeignKey::new()
+                        .name("api_key_event_id_fkey")
+                        .from_tbl(ApiKey::Table)
+                        .from_col(ApiKey::EventId)
+                        .to_tbl(Event::Table)
+                        .to_col(Event::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(ApiKey::Table).drop_column(ApiKey::EventId).to_owned())
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(LinkHistory::Table).drop_column(LinkHistory::EventId).to_owned())
+            .await?;
+
+        manager
+            .drop_index(Index::drop().table(User::Table).name("user_event_discord_snowflake_key").to_owned())
+            .await?;
+
+        manager
+            .drop_index(Index::drop().table(User::Table).name("user_event_minecraft_uuid_key").to_owned())
+            .await?;
+
+        // only works if no user is linked in more than one event
+        manager
+            .alter_table(Table::alter().table(User::Table).drop_column(User::EventId).to_owned())
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared("ALTER TABLE \"user\" ADD CONSTRAINT user_discord_snowflake_key UNIQUE (discord_snowflake);").await?;
+        db.execute_unprepared("ALTER TABLE \"user\" ADD CONSTRAINT user_minecraft_uuid_key UNIQUE (minecraft_uuid);").await?;
+
+        manager
+            .drop_table(Table::drop().table(Event::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+    Name,
+    #[sea_orm(iden = "guild_id")]
+    GuildId,
+    #[sea_orm(iden = "moderator_roles")]
+    ModeratorRoles,
+    #[sea_orm(iden = "webhook_url")]
+    WebhookUrl,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+    #[sea_orm(iden = "discord_snowflake")]
+    DiscordSnowflake,
+    #[sea_orm(iden = "minecraft_uuid")]
+    MinecraftUuid,
+}
+
+#[derive(DeriveIden)]
+enum LinkHistory {
+    Table,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+}
+
+#[derive(DeriveIden)]
+enum ApiKey {
+    Table,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+}
@@ -0,0 +1,175 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookSubscription::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookSubscription::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("uuid_generate_v4()")),
+                    )
+                    .col(
+                        ColumnDef::new(WebhookSubscription::EventId)
+                            .uuid()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookSubscription::Url)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookSubscription::Secret)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookSubscription::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("webhook_subscription_event_id_fkey")
+                        .from(WebhookSubscription::Table, WebhookSubscription::EventId)
+                        .to(Event::Table, Event::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookDelivery::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookDelivery::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::SubscriptionId)
+                            .uuid()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::Payload)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::NextAttemptAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::LastError)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::DeliveredAt)
+                            .timestamp_with_time_zone()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::FailedAt)
+                            .timestamp_with_time_zone()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDelivery::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("webhook_delivery_subscription_id_fkey")
+                        .from(WebhookDelivery::Table, WebhookDelivery::SubscriptionId)
+                        .to(WebhookSubscription::Table, WebhookSubscription::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(WebhookDelivery::Table)
+                .name("webhook_delivery_by_next_attempt_at")
+                .col(WebhookDelivery::NextAttemptAt)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebhookDelivery::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(WebhookSubscription::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum WebhookSubscription {
+    Table,
+    Id,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+    Url,
+    Secret,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum WebhookDelivery {
+    Table,
+    Id,
+    #[sea_orm(iden = "subscription_id")]
+    SubscriptionId,
+    Payload,
+    Attempts,
+    #[sea_orm(iden = "next_attempt_at")]
+    NextAttemptAt,
+    #[sea_orm(iden = "last_error")]
+    LastError,
+    #[sea_orm(iden = "delivered_at")]
+    DeliveredAt,
+    #[sea_orm(iden = "failed_at")]
+    FailedAt,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
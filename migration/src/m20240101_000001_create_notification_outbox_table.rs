@@ -0,0 +1,117 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NotificationOutbox::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NotificationOutbox::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::EventId)
+                            .uuid()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::Payload)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::NextAttemptAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::LastError)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::DeliveredAt)
+                            .timestamp_with_time_zone()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::FailedAt)
+                            .timestamp_with_time_zone()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(NotificationOutbox::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("notification_outbox_event_id_fkey")
+                        .from(NotificationOutbox::Table, NotificationOutbox::EventId)
+                        .to(Event::Table, Event::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(NotificationOutbox::Table)
+                .name("notification_outbox_by_next_attempt_at")
+                .col(NotificationOutbox::NextAttemptAt)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NotificationOutbox::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum NotificationOutbox {
+    Table,
+    Id,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+    Payload,
+    Attempts,
+    #[sea_orm(iden = "next_attempt_at")]
+    NextAttemptAt,
+    #[sea_orm(iden = "last_error")]
+    LastError,
+    #[sea_orm(iden = "delivered_at")]
+    DeliveredAt,
+    #[sea_orm(iden = "failed_at")]
+    FailedAt,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(User::OfflineUuid)
+                            .uuid()
+                            .null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        // MD5 of "OfflinePlayer:<name>" with the version and variant bits of a v3 UUID, the same as
+        // entity::user::offline_uuid, e.g. b50ad385-829d-3141-a216-7e7d7539ba7f for Notch
+        manager.get_connection().execute_unprepared(r#"
+            UPDATE "user" SET offline_uuid = (
+                SELECT overlay(overlay(hash PLACING '3' FROM 13)
+                    PLACING to_hex(8 | (('x' || substr(hash, 17, 1))::bit(4)::int & 3)) FROM 17)::uuid
+                FROM (SELECT md5('OfflinePlayer:' || minecraft_name) AS hash) hashes
+            )
+            WHERE minecraft_name IS NOT NULL AND edition = 'java';
+        "#).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(User::Table)
+                .name("user_by_event_offline_uuid")
+                .col(User::EventId)
+                .col(User::OfflineUuid)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop()
+                .table(User::Table)
+                .name("user_by_event_offline_uuid")
+                .to_owned()
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::OfflineUuid)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    EventId,
+    OfflineUuid,
+}
@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // every row of the user table is a link, so dropping the unique index lets a Discord user
+        // link several accounts
+        manager
+            .drop_index(Index::drop().table(User::Table).name("user_event_discord_snowflake_key").to_owned())
+            .await?;
+
+        manager
+            .create_index(Index::create()
+                .table(User::Table)
+                .name("user_by_event_discord_snowflake")
+                .col(User::EventId)
+                .col(User::DiscordSnowflake)
+                .to_owned()
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .add_column(
+                        ColumnDef::new(Event::MaxAccounts)
+                            .integer()
+                            .not_null()
+                            .default(1)
+                    )
+                    .add_column(
+                        ColumnDef::new(Event::AccountLimits)
+                            .string()
+                            .not_null()
+                            .default("")
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Event::Table)
+                    .drop_column(Event::MaxAccounts)
+                    .drop_column(Event::AccountLimits)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .drop_index(Index::drop().table(User::Table).name("user_by_event_discord_snowflake").to_owned())
+            .await?;
+
+        // fails while any Discord user has more than one account linked
+        manager
+            .create_index(Index::create()
+                .table(User::Table)
+                .name("user_event_discord_snowflake_key")
+                .col(User::EventId)
+                .col(User::DiscordSnowflake)
+                .unique()
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    EventId,
+    DiscordSnowflake,
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    MaxAccounts,
+    AccountLimits,
+}
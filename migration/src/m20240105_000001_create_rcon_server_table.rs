@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RconServer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RconServer::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("uuid_generate_v4()")),
+                    )
+                    .col(
+                        ColumnDef::new(RconServer::EventId)
+                            .uuid()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RconServer::Address)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RconServer::Password)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RconServer::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("rcon_server_event_id_fkey")
+                        .from(RconServer::Table, RconServer::EventId)
+                        .to(Event::Table, Event::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(Index::create()
+                .table(RconServer::Table)
+                .name("rcon_server_event_address_key")
+                .unique()
+                .col(RconServer::EventId)
+                .col(RconServer::Address)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RconServer::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Event {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum RconServer {
+    Table,
+    Id,
+    #[sea_orm(iden = "event_id")]
+    EventId,
+    Address,
+    Password,
+    #[sea_orm(iden = "created_at")]
+    CreatedAt,
+}
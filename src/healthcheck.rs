@@ -5,7 +5,9 @@ async fn main() -> anyhow::Result<()> {
 
     let expected_version = option_env!("VERSION").unwrap_or("unknown");
 
-    let resp = reqwest::get("http://localhost:3000/_health").await?
+    let url = mc_link_api::healthcheck_url()?;
+
+    let resp = reqwest::get(url).await?
         .json::<HealthCheckResponse>().await?;
 
     if resp.status != "ok" {